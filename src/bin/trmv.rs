@@ -6,8 +6,8 @@ use trctl::console::{Logger,View as _};
 //use trctl::console::Unprivileged;
 use tracing::{event, span, Level};
 use trctl::errors::*;
-use trctl::AddArgs;
 use trctl::TorrentLoc;
+use trctl::{AddArgs, OnDuplicate};
 use url::Url;
 
 const NAME: &str = "trmv";
@@ -29,6 +29,8 @@ pub enum Command {
         dldir: Option<PathBuf>,
         #[arg(long)]
         existing: bool,
+        #[arg(long = "label")]
+        labels: Vec<String>,
         path: PathBuf,
     },
     AddUrl {
@@ -36,6 +38,11 @@ pub enum Command {
         dldir: Option<PathBuf>,
         #[arg(long)]
         existing: bool,
+        #[arg(long = "label")]
+        labels: Vec<String>,
+        /// Header of the torrent file download as 'Name: value'
+        #[arg(long = "header")]
+        headers: Vec<String>,
         url: Url,
     },
 }
@@ -61,19 +68,36 @@ fn run_logged(builder: Builder<SyncRequest>) -> Result<()> {
                 ref dldir,
                 ref path,
                 existing,
+                ref labels,
             } => trmv.add(&AddArgs {
                 location: &TorrentLoc::Path(path.clone()),
                 dldir: dldir.as_ref(),
-                use_existing: existing,
+                use_existing: existing.then_some(true),
+                expect_hash: None,
+                no_overcommit: false,
+                labels,
+                on_duplicate: OnDuplicate::Log,
+                peer_limit: None,
+                priority: None,
+                headers: &[],
             }),
             AddUrl {
                 ref dldir,
                 ref url,
                 existing,
+                ref labels,
+                ref headers,
             } => trmv.add(&AddArgs {
                 location: &TorrentLoc::Url(url.clone()),
                 dldir: dldir.as_ref(),
-                use_existing: existing,
+                use_existing: existing.then_some(true),
+                expect_hash: None,
+                no_overcommit: false,
+                labels,
+                on_duplicate: OnDuplicate::Log,
+                peer_limit: None,
+                priority: None,
+                headers,
             }),
         };
         count += 1;
@@ -81,6 +105,8 @@ fn run_logged(builder: Builder<SyncRequest>) -> Result<()> {
         if let Err(ref err) = res {
             if let Some(NothingToDo(_)) = err.downcast_ref::<NothingToDo>() {
             } else if count <= 3 && trmv.view.ask_retry(err)? {
+                // the space might have been freed in the meantime
+                trmv.space_cache.clear();
                 continue
             }
         }
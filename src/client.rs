@@ -1,20 +1,29 @@
 #[allow(unused_imports)]
 use crate::config::{Builder, Config};
+use crate::display::parse_duration;
 use crate::errors::*;
+use crate::template::Template;
+use byte_unit::Byte;
 use clap::{Args, ValueEnum};
 use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
 use std::convert::TryFrom;
 use std::ops::{Deref, DerefMut};
 use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use time::macros::format_description;
 use tokio::runtime::Runtime;
+use tracing::{event, Level};
 use transmission_rpc::types::TorrentStatus;
 use transmission_rpc::types::{ErrorType, Priority};
 use transmission_rpc::types::{
-    FreeSpace, RpcResponse, RpcResponseArgument, SessionGet, Torrent, TorrentAddedOrDuplicate,
+    FreeSpace, RpcResponse, RpcResponseArgument, SessionGet, SessionSetArgs, Torrent,
+    TorrentAddedOrDuplicate, TorrentSetArgs,
 };
 pub use transmission_rpc::types::{Id, TorrentAction, TorrentAddArgs, TorrentGetField};
 use transmission_rpc::TransClient;
+use url::Url;
 
 #[derive(Debug)]
 pub enum TorrentAdded {
@@ -89,6 +98,21 @@ pub struct QueryCmd {
     /// Match moved and finished torrents
     #[arg(long)]
     pub cleanable: bool,
+    /// Match torrents that transmission considers stalled
+    #[arg(long)]
+    pub stalled: bool,
+    /// Match torrents with a tracker whose last announce failed or timed out
+    #[arg(long)]
+    pub tracker_failed: bool,
+    /// Match torrents that are downloading or uploading right now
+    #[arg(long)]
+    pub active: bool,
+    /// Allow rm and erase to match every torrent when not interactive
+    #[arg(long)]
+    pub i_really_mean_it: bool,
+    /// With --cleanable also require at least this upload ratio
+    #[arg(long, requires = "cleanable")]
+    pub ratio: Option<f32>,
     /// All the strings have to match instead of one
     #[arg(long)]
     pub and: bool,
@@ -101,26 +125,232 @@ pub struct QueryCmd {
     /// Print in reverse
     #[arg(long, short)]
     pub reverse: bool,
+    /// Only the N oldest matches by the time added, oldest first
+    #[arg(long, value_name = "N", conflicts_with_all = ["sort", "newest"])]
+    pub oldest: Option<usize>,
+    /// Only the N newest matches by the time added, newest first
+    #[arg(long, value_name = "N", conflicts_with = "sort")]
+    pub newest: Option<usize>,
     /// Match ids
     #[arg(long)]
     pub ids: Vec<i64>,
     /// Match hashes
     #[arg(long)]
     pub hsh: Vec<String>,
+    /// Match the ids and hashes in a file, one per line. - reads stdin.
+    #[arg(long)]
+    pub ids_from: Option<PathBuf>,
     /// Match trackers
     #[arg(long)]
     pub trackers: Vec<String>,
+    /// Skip torrents with any tracker matching. Torrents without trackers are not skipped
+    #[arg(long = "exclude-tracker")]
+    pub exclude_trackers: Vec<String>,
+    /// Match torrents with all these labels
+    #[arg(long = "label")]
+    pub labels: Vec<String>,
     /// Match status(es)
     #[arg(long)]
     pub status: Vec<MyTorrentStatus>,
+    /// Match seeding or queued to seed. Adds to --status
+    #[arg(long)]
+    pub seeding: bool,
+    /// Match downloading or queued to download. Adds to --status
+    #[arg(long)]
+    pub downloading: bool,
+    /// Match queued to download, to seed or to verify. Adds to --status
+    #[arg(long)]
+    pub queued: bool,
+    /// Show the Seeds and Peers columns, the most seeders and leechers reported by a tracker
+    #[arg(long)]
+    pub swarm: bool,
+    /// Show the Seed column, the time seeded. Also shown when sorting by it.
+    #[arg(long)]
+    pub seed_time: bool,
+    /// Print only the names of the matched torrents
+    #[arg(long)]
+    pub names_only: bool,
+    /// Terminate the names with NUL instead of newline, for xargs -0
+    #[arg(long, requires = "names_only")]
+    pub print0: bool,
+    /// Print only the names escaped for zsh completion
+    #[arg(long)]
+    pub completion_format: bool,
+    /// Match query strings that are 40 hex characters as hashes and integers as ids
+    #[arg(long)]
+    pub smart_ids: bool,
+    /// Print a stable format for scripts instead of the table. A line per torrent with the tab
+    /// separated fields: id, hash, status, `percent_done` (0-1), `size_when_done` (bytes),
+    /// `left_until_done` (bytes), `rate_upload` (B/s), `rate_download` (B/s), `upload_ratio`,
+    /// `added_date` (epoch), `download_dir`, name and `seconds_seeding`. Missing values are `-`.
+    /// Backslash, tab, CR and newline are escaped as `\\`, `\t`, `\r` and `\n`. New fields are only
+    /// ever appended.
+    #[arg(long, conflicts_with_all = ["names_only", "completion_format", "group_by"])]
+    pub porcelain: bool,
+    /// Print each torrent with a template like '{id}\t{name}'. The fields are id, hash, name,
+    /// done, have, size, eta, up, down, ratio, age, `seed_time`, status and dir. {{ and }} are
+    /// literal braces.
+    #[arg(
+        long,
+        value_parser = Template::parse,
+        conflicts_with_all = ["names_only", "completion_format", "group_by", "porcelain"]
+    )]
+    pub template: Option<Template>,
+    /// Leave out the header line of the table
+    #[arg(long)]
+    pub no_header: bool,
+    /// Print subtotals per group instead of the torrents
+    #[arg(long)]
+    pub group_by: Option<GroupBy>,
+    /// Print only the torrents that no earlier query --new has printed. Needs the sqlite db.
+    /// Only the query command does this.
+    #[arg(long)]
+    pub new: bool,
+    /// Print only the newest torrent of each group with a count like (x3) after the name.
+    /// Only the query command does this.
+    #[arg(long)]
+    pub dedupe_by: Option<DedupeBy>,
+    /// Write the results to a file instead of stdout
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+    /// Match torrents at least this big, e.g. 5GiB
+    #[arg(long, value_parser = parse_byte)]
+    pub min_size: Option<Byte>,
+    /// Match torrents at most this big, e.g. 20GiB
+    #[arg(long, value_parser = parse_byte)]
+    pub max_size: Option<Byte>,
+    /// Match torrents at least this many percent done
+    #[arg(long)]
+    pub min_done: Option<f32>,
+    /// Match torrents at most this many percent done
+    #[arg(long)]
+    pub max_done: Option<f32>,
+    /// Match torrents connected to at least this many peers
+    #[arg(long)]
+    pub min_peers: Option<i64>,
+    /// Match torrents connected to at most this many peers. 0 finds the ones without any
+    #[arg(long)]
+    pub max_peers: Option<i64>,
+    /// Match torrents expected to finish in at least this long, in seconds or e.g. 30m
+    #[arg(long, value_parser = parse_eta)]
+    pub min_eta: Option<i64>,
+    /// Match torrents expected to finish within this long, in seconds or e.g. 1h
+    #[arg(long, value_parser = parse_eta)]
+    pub max_eta: Option<i64>,
+    /// Match torrents that finished downloading before a date (YYYY-MM-DD in UTC) or a time
+    /// ago, e.g. 2w
+    #[arg(long, value_parser = parse_time)]
+    pub completed_before: Option<i64>,
+    /// Match torrents active after an epoch, a date (YYYY-MM-DD in UTC) or a time ago, e.g. 10m
+    #[arg(long, value_parser = parse_time)]
+    pub active_since: Option<i64>,
     /// Query names
     pub strs: Vec<String>,
 }
 
+impl QueryCmd {
+    /// Move the query strings that look like hashes or ids to `hsh` and `ids` if `smart_ids` is
+    /// set
+    pub fn apply_smart_ids(&mut self) {
+        if !self.smart_ids {
+            return;
+        }
+        for s in std::mem::take(&mut self.strs) {
+            if s.len() == 40 && s.chars().all(|c| c.is_ascii_hexdigit()) {
+                self.hsh.push(s.to_lowercase());
+            } else if let Ok(id) = s.parse::<i64>() {
+                self.ids.push(id);
+            } else {
+                self.strs.push(s);
+            }
+        }
+    }
+
+    /// Add the ids and hashes of `ids_from` to `ids` and `hsh`
+    pub fn read_ids_from(&mut self, prompting: bool) -> Result<()> {
+        let Some(ref path) = self.ids_from else {
+            return Ok(());
+        };
+        if path.as_os_str() == "-" {
+            if prompting {
+                bail!("--ids-from - uses up stdin which the prompts need, give --yes or a file");
+            }
+            return self.read_ids(std::io::stdin().lock());
+        }
+        let file =
+            std::fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        self.read_ids(std::io::BufReader::new(file))
+    }
+
+    // Nothing read is an error as no ids would match every torrent
+    fn read_ids(&mut self, input: impl std::io::BufRead) -> Result<()> {
+        let mut count = 0;
+        for line in input.lines() {
+            let line = line?;
+            let token = line.trim();
+            if token.is_empty() {
+                continue;
+            }
+            count += 1;
+            if token.len() == 40 && token.chars().all(|c| c.is_ascii_hexdigit()) {
+                self.hsh.push(token.to_lowercase());
+            } else if let Ok(id) = token.parse::<i64>() {
+                self.ids.push(id);
+            } else {
+                bail!("Not an id or a hash: {}", token);
+            }
+        }
+        if count == 0 {
+            return Err(anyhow!(NoMatches).context("No ids or hashes in --ids-from"));
+        }
+        Ok(())
+    }
+
+    /// Whether any of the status options is given
+    #[must_use]
+    pub fn has_status(&self) -> bool {
+        !self.status.is_empty() || self.seeding || self.downloading || self.queued
+    }
+}
+
+// Same parsing as the sizes in the config
+fn parse_byte(s: &str) -> std::result::Result<Byte, String> {
+    Byte::parse_str(s, true).map_err(|e| e.to_string())
+}
+
+// Seconds
+fn parse_eta(s: &str) -> std::result::Result<i64, String> {
+    s.parse::<i64>()
+        .ok()
+        .or_else(|| parse_duration(s))
+        .ok_or_else(|| "expected seconds or a duration like 1h".to_string())
+}
+
+// Seconds since the epoch
+fn parse_time(s: &str) -> std::result::Result<i64, String> {
+    if let Ok(epoch) = s.parse::<i64>() {
+        return Ok(epoch);
+    }
+    if let Some(ago) = parse_duration(s) {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|e| e.to_string())?
+            .as_secs();
+        return Ok(i64::try_from(now).map_err(|e| e.to_string())? - ago);
+    }
+    let date = time::Date::parse(s, format_description!("[year]-[month]-[day]")).map_err(|e| {
+        format!("expected YYYY-MM-DD, seconds since the epoch or a duration like 2w: {e}")
+    })?;
+    Ok(date.midnight().assume_utc().unix_timestamp())
+}
+
 #[derive(Debug)]
 pub struct Client<T> {
     pub imp: T,
     pub dldirs: Vec<PathBuf>,
+    /// Used when the query does not give a sort
+    pub default_sort: Option<Sort>,
+    pub default_reverse: bool,
 }
 
 impl<T: TorrentCli> Deref for Client<T> {
@@ -162,13 +392,81 @@ impl From<TorrentStatus> for MyTorrentStatus {
     }
 }
 
-#[derive(ValueEnum, Debug, Clone, Copy)]
+#[derive(ValueEnum, Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Sort {
     Id,
     Name,
     Urate,
     Drate,
     Size,
+    Age,
+    /// Longest seeding first
+    SeedTime,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum GroupBy {
+    Dldir,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum DedupeBy {
+    Name,
+}
+
+/// Bandwidth groups came in transmission 4.0
+pub const GROUP_RPC_VERSION: i32 = 17;
+
+/// Transmission gives -1 for a ratio not available and -2 for an infinite one
+const RATIO_INFINITE_BELOW: f32 = -1.5;
+
+/// Width of the optional hash column
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq)]
+pub enum HashColumn {
+    /// The first 8 characters
+    #[default]
+    Short,
+    /// All 40 characters
+    Full,
+}
+
+impl HashColumn {
+    #[must_use]
+    pub const fn width(self) -> usize {
+        match self {
+            Self::Short => 8,
+            Self::Full => 40,
+        }
+    }
+}
+
+/// How reports are printed
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq)]
+pub enum OutputFormat {
+    /// For humans
+    #[default]
+    Text,
+    /// A single json object
+    Json,
+}
+
+/// Bandwidth priority of a torrent
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum PriorityLevel {
+    High,
+    Normal,
+    Low,
+}
+
+impl From<PriorityLevel> for Priority {
+    fn from(level: PriorityLevel) -> Self {
+        match level {
+            PriorityLevel::High => Priority::High,
+            PriorityLevel::Normal => Priority::Normal,
+            PriorityLevel::Low => Priority::Low,
+        }
+    }
 }
 
 impl Default for Sort {
@@ -178,15 +476,22 @@ impl Default for Sort {
 }
 
 impl<T: TorrentCli> Client<T> {
+    // Ties are always in id order so that the output is deterministic
+    fn sort_by_maybe_reverse<F>(torrents: &mut [Torrent], mut cmp: F, reverse: bool)
+    where
+        F: FnMut(&Torrent, &Torrent) -> std::cmp::Ordering,
+    {
+        torrents.sort_unstable_by(|x, y| {
+            let ord = cmp(x, y);
+            if reverse { ord.reverse() } else { ord }.then_with(|| x.id.cmp(&y.id))
+        });
+    }
+
     fn sort_maybe_reverse<K: Ord, F>(torrents: &mut [Torrent], mut f: F, reverse: bool)
     where
         F: FnMut(&Torrent) -> K,
     {
-        if reverse {
-            torrents.sort_unstable_by_key(|x| std::cmp::Reverse(f(x)));
-        } else {
-            torrents.sort_unstable_by_key(f);
-        }
+        Self::sort_by_maybe_reverse(torrents, |x, y| f(x).cmp(&f(y)), reverse);
     }
 
     // TODO
@@ -199,38 +504,96 @@ impl<T: TorrentCli> Client<T> {
 
     pub fn sort(torrents: &mut [Torrent], sort: Sort, reverse: bool) {
         match sort {
-            Sort::Id => {
-                if reverse {
-                    torrents.sort_unstable_by(|x, y| y.id.cmp(&x.id));
-                } else {
-                    torrents.sort_unstable_by(|x, y| x.id.cmp(&y.id));
-                }
-            }
+            Sort::Id => Self::sort_maybe_reverse(torrents, |x| x.id, reverse),
             Sort::Name => {
-                if reverse {
-                    torrents.sort_unstable_by(|x, y| y.name.cmp(&x.name));
-                } else {
-                    torrents.sort_unstable_by(|x, y| x.name.cmp(&y.name));
-                }
+                Self::sort_by_maybe_reverse(torrents, |x, y| x.name.cmp(&y.name), reverse);
             }
             Sort::Urate => Self::sort_maybe_reverse(torrents, |x| x.rate_upload, reverse),
             Sort::Drate => Self::sort_maybe_reverse(torrents, |x| x.rate_download, reverse),
             Sort::Size => Self::sort_maybe_reverse(torrents, |x| x.size_when_done, reverse),
+            // newest first, unknown last
+            Sort::Age => {
+                Self::sort_maybe_reverse(torrents, |x| std::cmp::Reverse(x.added_date), reverse);
+            }
+            // unknown last
+            Sort::SeedTime => Self::sort_maybe_reverse(
+                torrents,
+                |x| std::cmp::Reverse(x.seconds_seeding),
+                reverse,
+            ),
         }
     }
 
+    /// One rpc call per distinct destination however the ids are split in `pairs`
+    pub fn set_locations(&mut self, pairs: Vec<(Vec<String>, String)>, mv: bool) -> Result<()> {
+        let mut by_location: Vec<(String, Vec<String>)> = Vec::new();
+        for (ids, location) in pairs {
+            match by_location.iter_mut().find(|(l, _)| *l == location) {
+                Some((_, all)) => all.extend(ids),
+                None => by_location.push((location, ids)),
+            }
+        }
+        for (location, ids) in by_location {
+            self.imp.set_location(ids, mv, location)?;
+        }
+        Ok(())
+    }
+
     // Note that filtering might not work if correct fields are not selected
     pub fn torrent_query_sort(
         &mut self,
         fields: Option<Vec<TorrentGetField>>,
         qcmd: &QueryCmd,
     ) -> Result<Vec<Torrent>> {
+        Ok(self.torrent_query_sort_total(fields, qcmd)?.0)
+    }
+
+    /// Also returns the count of torrents before filtering
+    pub fn torrent_query_sort_total(
+        &mut self,
+        fields: Option<Vec<TorrentGetField>>,
+        qcmd: &QueryCmd,
+    ) -> Result<(Vec<Torrent>, usize)> {
         let torrents = self.do_torrent_query(fields, qcmd)?;
+        let total = torrents.len();
         let filter = TorrentFilter::new(self.dldirs.as_slice(), qcmd)?;
+        let unknown = filter.count_unknown(&torrents);
+        // Older daemons omit some fields and such torrents never match
+        if unknown > 0 && unknown * 2 >= total {
+            event!(
+                Level::WARN,
+                "{unknown} of {total} torrents lack fields the query needs, the daemon may be too old"
+            );
+        } else if unknown > 0 {
+            event!(
+                Level::DEBUG,
+                "{unknown} of {total} torrents lack fields the query needs"
+            );
+        }
         let mut filtered: Vec<Torrent> = filter.filter_torrents(torrents)?.collect();
 
-        Self::sort(&mut filtered, qcmd.sort.unwrap_or_default(), qcmd.reverse);
-        Ok(filtered)
+        // reverse flips the configured direction
+        let (sort, reverse) = match qcmd.sort {
+            Some(sort) => (sort, qcmd.reverse),
+            None => (
+                self.default_sort.unwrap_or_default(),
+                qcmd.reverse != self.default_reverse,
+            ),
+        };
+        Self::sort(&mut filtered, sort, reverse);
+
+        let take = match (qcmd.oldest, qcmd.newest) {
+            (Some(n), _) => Some((n, true)),
+            (None, Some(n)) => Some((n, false)),
+            (None, None) => None,
+        };
+        if let Some((n, oldest)) = take {
+            // an unknown age is neither old nor new
+            filtered.retain(|t| t.added_date.is_some());
+            Self::sort(&mut filtered, Sort::Age, oldest);
+            filtered.truncate(n);
+        }
+        Ok((filtered, total))
     }
 
     fn do_torrent_query(
@@ -259,6 +622,34 @@ impl<T: TorrentCli> Client<T> {
         filter.filter_torrents(torrents)
     }
 
+    /// Retries `session_get` until the daemon answers or the timeout has passed. Only
+    /// connection errors are retried.
+    pub fn wait_for_daemon(&mut self, timeout: Duration) -> Result<()> {
+        let start = Instant::now();
+        let mut delay = Duration::from_millis(100);
+        let mut attempt = 1;
+        loop {
+            match self.session_get() {
+                Ok(_) => return Ok(()),
+                Err(err)
+                    if err.downcast_ref::<RpcConnection>().is_some()
+                        && start.elapsed() + delay < timeout =>
+                {
+                    event!(
+                        Level::DEBUG,
+                        "waiting for the daemon, attempt {attempt}: {err:#}"
+                    );
+                    std::thread::sleep(delay);
+                    delay = (delay * 2).min(Duration::from_secs(2));
+                    attempt += 1;
+                }
+                Err(err) => {
+                    return Err(err.context(format!("daemon not up after {attempt} attempts")))
+                }
+            }
+        }
+    }
+
     pub fn torrent_get(
         &mut self,
         fields: Option<Vec<TorrentGetField>>,
@@ -286,13 +677,41 @@ pub trait TorrentCli {
     fn torrent_action(&mut self, ids: Vec<String>, action: TorrentAction) -> Result<()>;
 
     fn set_location(&mut self, ids: Vec<String>, mv: bool, location: String) -> Result<()>;
+
+    fn set_priority(&mut self, ids: Vec<String>, priority: PriorityLevel) -> Result<()>;
+
+    /// Set the download priority of the files at the given indices of a torrent
+    fn set_file_priority(
+        &mut self,
+        id: String,
+        files: Vec<usize>,
+        level: PriorityLevel,
+    ) -> Result<()>;
+
+    /// Leave the files at the given indices of a torrent undownloaded
+    fn set_files_unwanted(&mut self, id: String, files: Vec<usize>) -> Result<()>;
+
+    /// Whether the torrents are subject to the session wide speed limits
+    fn set_honors_session_limits(&mut self, ids: Vec<String>, honors: bool) -> Result<()>;
+
+    /// Needs rpc version `GROUP_RPC_VERSION`
+    fn set_group(&mut self, ids: Vec<String>, group: String) -> Result<()>;
+
+    /// Set the session wide speed limits in KB/s. Zero disables the limit and None leaves it as
+    /// is.
+    fn session_set_speed(&mut self, down: Option<i64>, up: Option<i64>) -> Result<()>;
+
+    /// The session wide speed limits in KB/s as the daemon has them, None when disabled
+    fn session_get_speed(&mut self) -> Result<(Option<i64>, Option<i64>)>;
 }
 
 #[derive(Debug)]
 pub struct TorrentFilter<'a> {
     pub dldirs: &'a [PathBuf],
     pub trackers: Vec<Regex>,
+    pub exclude_trackers: Vec<Regex>,
     pub strs: Vec<Regex>,
+    pub statuses: Vec<MyTorrentStatus>,
     pub qcmd: &'a QueryCmd,
 }
 
@@ -316,28 +735,51 @@ impl<'x> TorrentFilter<'x> {
             })
             .collect::<Result<Vec<regex::Regex>>>()?;
 
-        let trackers = qcmd
-            .trackers
-            .iter()
-            .map(|s| {
-                let mut builder = if qcmd.exact {
-                    RegexBuilder::new(&format!("^{}$", regex::escape(s)))
-                } else {
-                    RegexBuilder::new(&regex::escape(s))
-                };
+        let tracker_regexes = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|s| {
+                    let mut builder = if qcmd.exact {
+                        RegexBuilder::new(&format!("^{}$", regex::escape(s)))
+                    } else {
+                        RegexBuilder::new(&regex::escape(s))
+                    };
+
+                    if !qcmd.use_case && !s.chars().any(char::is_uppercase) {
+                        builder.case_insensitive(true);
+                    }
 
-                if !qcmd.use_case && !s.chars().any(char::is_uppercase) {
-                    builder.case_insensitive(true);
-                }
+                    builder.build().context("regex build failed")
+                })
+                .collect::<Result<Vec<regex::Regex>>>()
+        };
+        let trackers = tracker_regexes(&qcmd.trackers)?;
+        let exclude_trackers = tracker_regexes(&qcmd.exclude_trackers)?;
 
-                builder.build().context("regex build failed")
-            })
-            .collect::<Result<Vec<regex::Regex>>>()?;
+        let mut statuses = qcmd.status.clone();
+        if qcmd.seeding {
+            statuses.extend_from_slice(&[MyTorrentStatus::Seeding, MyTorrentStatus::QueuedToSeed]);
+        }
+        if qcmd.downloading {
+            statuses.extend_from_slice(&[
+                MyTorrentStatus::Downloading,
+                MyTorrentStatus::QueuedToDownload,
+            ]);
+        }
+        if qcmd.queued {
+            statuses.extend_from_slice(&[
+                MyTorrentStatus::QueuedToDownload,
+                MyTorrentStatus::QueuedToSeed,
+                MyTorrentStatus::QueuedToVerify,
+            ]);
+        }
 
         Ok(Self {
             dldirs,
             trackers,
+            exclude_trackers,
             strs,
+            statuses,
             qcmd,
         })
     }
@@ -357,6 +799,19 @@ impl<'x> TorrentFilter<'x> {
         Ok(iter)
     }
 
+    /// The torrents that can't be matched because a field the query needs is missing
+    pub fn count_unknown<I>(&self, torrents: I) -> usize
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Torrent>,
+    {
+        torrents
+            .into_iter()
+            .filter(|t| self.torrent_filter(t.borrow()).is_none())
+            .count()
+    }
+
+    #[allow(clippy::too_many_lines)]
     fn torrent_filter(&self, tor: &Torrent) -> Option<bool> {
         if !self.strs.is_empty() {
             if self.qcmd.and {
@@ -396,28 +851,41 @@ impl<'x> TorrentFilter<'x> {
                     }
                 }
             } else {
-                for tracker in tor.trackers.as_ref()? {
-                    found = true;
-                    for tqs in &self.trackers {
-                        if !tqs.is_match(&tracker.announce) {
-                            found = false;
-                            break;
-                        }
-                    }
-                    if found {
-                        break;
-                    }
-                }
+                // Every pattern has to match some tracker. Not necessarily the same one.
+                let trackers = tor.trackers.as_ref()?;
+                found = self.trackers.iter().all(|tqs| {
+                    trackers
+                        .iter()
+                        .any(|tracker| tqs.is_match(&tracker.announce))
+                });
             }
             if !found {
                 return Some(false);
             }
         }
 
+        if !self.exclude_trackers.is_empty() {
+            let trackers = tor.trackers.as_ref()?;
+            if self.exclude_trackers.iter().any(|tqs| {
+                trackers
+                    .iter()
+                    .any(|tracker| tqs.is_match(&tracker.announce))
+            }) {
+                return Some(false);
+            }
+        }
+
+        if !self.qcmd.labels.is_empty() {
+            let labels = tor.labels.as_ref()?;
+            if !self.qcmd.labels.iter().all(|l| labels.contains(l)) {
+                return Some(false);
+            }
+        }
+
         {
-            if !self.qcmd.status.is_empty() {
+            if !self.statuses.is_empty() {
                 let mut matches = false;
-                for status in &self.qcmd.status {
+                for status in &self.statuses {
                     if MyTorrentStatus::from(tor.status?) == *status {
                         matches = true;
                         break;
@@ -447,6 +915,79 @@ impl<'x> TorrentFilter<'x> {
             return Some(false);
         }
 
+        if self.qcmd.stalled && !tor.is_stalled? {
+            return Some(false);
+        }
+
+        if self.qcmd.tracker_failed && !Self::tracker_failed(tor)? {
+            return Some(false);
+        }
+
+        if self.qcmd.active
+            && !(tor.rate_download.is_some_and(|r| r > 0) || tor.rate_upload.is_some_and(|r| r > 0))
+        {
+            return Some(false);
+        }
+
+        if self.qcmd.min_size.is_some() || self.qcmd.max_size.is_some() {
+            let size = u64::try_from(tor.size_when_done?).ok()?;
+            if self.qcmd.min_size.is_some_and(|min| size < min.as_u64())
+                || self.qcmd.max_size.is_some_and(|max| size > max.as_u64())
+            {
+                return Some(false);
+            }
+        }
+
+        if self.qcmd.min_done.is_some() || self.qcmd.max_done.is_some() {
+            let done = tor.percent_done? * 100.0;
+            if self.qcmd.min_done.is_some_and(|min| done < min)
+                || self.qcmd.max_done.is_some_and(|max| done > max)
+            {
+                return Some(false);
+            }
+        }
+
+        if self.qcmd.min_peers.is_some() || self.qcmd.max_peers.is_some() {
+            let peers = tor.peers_connected?;
+            if self.qcmd.min_peers.is_some_and(|min| peers < min)
+                || self.qcmd.max_peers.is_some_and(|max| peers > max)
+            {
+                return Some(false);
+            }
+        }
+
+        if self.qcmd.min_eta.is_some() || self.qcmd.max_eta.is_some() {
+            // -1 when done and -2 when unknown
+            let eta = tor.eta?;
+            if eta < 0
+                || self.qcmd.min_eta.is_some_and(|min| eta < min)
+                || self.qcmd.max_eta.is_some_and(|max| eta > max)
+            {
+                return Some(false);
+            }
+        }
+
+        // still downloading if not done
+        if let Some(before) = self.qcmd.completed_before {
+            if !tor
+                .done_date
+                .map(|done| done.timestamp())
+                .is_some_and(|done| done > 0 && done < before)
+            {
+                return Some(false);
+            }
+        }
+
+        if let Some(since) = self.qcmd.active_since {
+            if !tor
+                .activity_date
+                .map(|active| active.timestamp())
+                .is_some_and(|active| active > 0 && active >= since)
+            {
+                return Some(false);
+            }
+        }
+
         // TODO have to check status too?
         if self.qcmd.move_aborted
             && !(tor.left_until_done? == 0 && self.in_dl_dir(tor)? && tor.size_when_done? != 0)
@@ -469,7 +1010,20 @@ impl<'x> TorrentFilter<'x> {
         Some(true)
     }
 
+    // Trackers that have not announced yet have not failed either
+    fn tracker_failed(tor: &Torrent) -> Option<bool> {
+        Some(tor.tracker_stats.as_ref()?.iter().any(|stat| {
+            stat.has_announced && (!stat.last_announce_succeeded || stat.last_announce_timed_out)
+        }))
+    }
+
     fn filter_is_cleanable(&self, tor: &Torrent) -> Option<bool> {
+        if let Some(min) = self.qcmd.ratio {
+            let ratio = tor.upload_ratio?;
+            if ratio < min && ratio > RATIO_INFINITE_BELOW {
+                return Some(false);
+            }
+        }
         Some(
             tor.is_finished?
                 && !self.in_dl_dir(tor)?
@@ -487,6 +1041,70 @@ impl<'x> TorrentFilter<'x> {
 pub struct SyncRequest {
     pub client: TransClient,
     pub tokio: Runtime,
+    /// For `raw_rpc` as the client does not expose these
+    pub url: Url,
+    pub auth: Option<(String, String)>,
+}
+
+const SESSION_ID_HEADER: &str = "X-Transmission-Session-Id";
+
+impl SyncRequest {
+    /// Sends a request bypassing the typed wrappers. Neither the arguments nor the response are
+    /// validated.
+    pub fn raw_rpc(
+        &self,
+        method: &str,
+        arguments: &serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let body = serde_json::json!({ "method": method, "arguments": arguments }).to_string();
+        self.tokio.block_on(async {
+            let http = reqwest::Client::new();
+            let mut session_id = String::new();
+            loop {
+                let mut req = http
+                    .post(self.url.clone())
+                    .header(SESSION_ID_HEADER, &session_id)
+                    .body(body.clone());
+                if let Some((ref user, ref password)) = self.auth {
+                    req = req.basic_auth(user, Some(password));
+                }
+                let res = req.send().await.context("rpc call")?;
+                // the first request only gets us the session id
+                if res.status() == reqwest::StatusCode::CONFLICT && session_id.is_empty() {
+                    session_id = res
+                        .headers()
+                        .get(SESSION_ID_HEADER)
+                        .context("no session id in the response")?
+                        .to_str()?
+                        .to_string();
+                    continue;
+                }
+                let text = res.error_for_status()?.text().await?;
+                return serde_json::from_str(&text).context("response is not json");
+            }
+        })
+    }
+
+    // the ids field of the args is private so they are given separately
+    fn torrent_set(&mut self, args: TorrentSetArgs, ids: Vec<String>) -> Result<()> {
+        call(
+            &self.tokio,
+            self.client
+                .torrent_set(args, Some(ids.into_iter().map(Id::Hash).collect())),
+        )?;
+        Ok(())
+    }
+}
+
+/// The runtime that the blocking requests run on
+pub fn new_runtime() -> Result<Runtime> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context(
+            "building the async runtime failed. This is a limit of the environment, such as \
+             the number of open files, not a problem with the config",
+        )
 }
 
 fn call<RS, C>(tokio: &Runtime, f: C) -> Result<RS>
@@ -495,7 +1113,13 @@ where
     RS: RpcResponseArgument,
 {
     tokio.block_on(async {
-        let res = f.await.map_err(|e| anyhow!("rpc call: {:#}", e))?;
+        let res = f.await.map_err(|e| {
+            if is_transport_error(&*e) {
+                anyhow!(RpcConnection(format!("{e:#}")))
+            } else {
+                anyhow!(e)
+            }
+        })?;
         if !res.is_ok() {
             bail!("rpc request failed with: '{}'", res.result);
         }
@@ -507,6 +1131,18 @@ where
     })
 }
 
+/// Only failing to reach the daemon is worth a retry, not a bad response
+fn is_transport_error(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+            return err.is_connect() || err.is_timeout();
+        }
+        source = err.source();
+    }
+    false
+}
+
 impl TorrentCli for SyncRequest {
     fn torrent_add(&mut self, args: TorrentAddArgs) -> Result<TorrentAdded> {
         TorrentAdded::try_from(call(&self.tokio, self.client.torrent_add(args))?)
@@ -550,6 +1186,45 @@ impl TorrentCli for SyncRequest {
         Ok(())
     }
 
+    fn set_priority(&mut self, ids: Vec<String>, priority: PriorityLevel) -> Result<()> {
+        let mut args = TorrentSetArgs::default();
+        args.bandwidth_priority = Some(priority.into());
+        self.torrent_set(args, ids)
+    }
+
+    fn set_file_priority(
+        &mut self,
+        id: String,
+        files: Vec<usize>,
+        level: PriorityLevel,
+    ) -> Result<()> {
+        let mut args = TorrentSetArgs::default();
+        match level {
+            PriorityLevel::High => args.priority_high = Some(files),
+            PriorityLevel::Normal => args.priority_normal = Some(files),
+            PriorityLevel::Low => args.priority_low = Some(files),
+        }
+        self.torrent_set(args, vec![id])
+    }
+
+    fn set_files_unwanted(&mut self, id: String, files: Vec<usize>) -> Result<()> {
+        let mut args = TorrentSetArgs::default();
+        args.files_unwanted = Some(files);
+        self.torrent_set(args, vec![id])
+    }
+
+    fn set_honors_session_limits(&mut self, ids: Vec<String>, honors: bool) -> Result<()> {
+        let mut args = TorrentSetArgs::default();
+        args.honors_session_limits = Some(honors);
+        self.torrent_set(args, ids)
+    }
+
+    fn set_group(&mut self, ids: Vec<String>, group: String) -> Result<()> {
+        let mut args = TorrentSetArgs::default();
+        args.group = Some(group);
+        self.torrent_set(args, ids)
+    }
+
     fn torrent_get(
         &mut self,
         fields: Option<Vec<TorrentGetField>>,
@@ -557,11 +1232,78 @@ impl TorrentCli for SyncRequest {
     ) -> Result<Vec<Torrent>> {
         Ok(call(&self.tokio, self.client.torrent_get(fields, oids))?.torrents)
     }
+
+    fn session_set_speed(&mut self, down: Option<i64>, up: Option<i64>) -> Result<()> {
+        let limit = |speed: Option<i64>| -> Result<(Option<i32>, Option<bool>)> {
+            match speed {
+                None => Ok((None, None)),
+                Some(0) => Ok((None, Some(false))),
+                Some(s) => Ok((
+                    Some(i32::try_from(s).context("speed limit overflow")?),
+                    Some(true),
+                )),
+            }
+        };
+        let (speed_limit_down, speed_limit_down_enabled) = limit(down)?;
+        let (speed_limit_up, speed_limit_up_enabled) = limit(up)?;
+        call(
+            &self.tokio,
+            self.client.session_set(SessionSetArgs {
+                speed_limit_down,
+                speed_limit_down_enabled,
+                speed_limit_up,
+                speed_limit_up_enabled,
+                ..SessionSetArgs::default()
+            }),
+        )?;
+        Ok(())
+    }
+
+    // SessionGet of transmission_rpc does not have the speed limits
+    fn session_get_speed(&mut self) -> Result<(Option<i64>, Option<i64>)> {
+        let res = self.raw_rpc(
+            "session-get",
+            &serde_json::json!({ "fields": [
+                "speed-limit-down",
+                "speed-limit-down-enabled",
+                "speed-limit-up",
+                "speed-limit-up-enabled",
+            ] }),
+        )?;
+        if res["result"] != "success" {
+            bail!("rpc request failed with: '{}'", res["result"]);
+        }
+        let args = &res["arguments"];
+        let limit = |dir: &str| -> Result<Option<i64>> {
+            let enabled = args[format!("speed-limit-{dir}-enabled")]
+                .as_bool()
+                .context("no speed limit in the response")?;
+            let speed = args[format!("speed-limit-{dir}")]
+                .as_i64()
+                .context("no speed limit in the response")?;
+            Ok(enabled.then_some(speed))
+        };
+        Ok((limit("down")?, limit("up")?))
+    }
 }
 
 pub struct MockRequest {
     pub mock_data: Vec<Torrent>,
     pub fail_rpc: bool,
+    /// Times `torrent_get` and `session_get` fail to connect before succeeding
+    pub fail_connection: usize,
+    pub session_speed: Vec<(Option<i64>, Option<i64>)>,
+    pub priorities: Vec<(Vec<String>, PriorityLevel)>,
+    pub file_priorities: Vec<(String, Vec<usize>, PriorityLevel)>,
+    pub files_unwanted: Vec<(String, Vec<usize>)>,
+    pub session_limits: Vec<(Vec<String>, bool)>,
+    pub groups: Vec<(Vec<String>, String)>,
+    /// Reported by `session_get`
+    pub rpc_version: i32,
+    /// `torrent_add` finds the torrent already added
+    pub duplicate: bool,
+    pub locations: Vec<(Vec<String>, String)>,
+    pub fail_set_location: bool,
 }
 
 impl Default for MockRequest {
@@ -577,6 +1319,17 @@ impl Default for MockRequest {
             ]
             .to_vec(),
             fail_rpc: false,
+            fail_connection: 0,
+            locations: Vec::new(),
+            fail_set_location: false,
+            session_speed: vec![],
+            priorities: vec![],
+            file_priorities: vec![],
+            files_unwanted: vec![],
+            session_limits: vec![],
+            groups: vec![],
+            rpc_version: 7,
+            duplicate: false,
         }
     }
 }
@@ -587,6 +1340,13 @@ impl TorrentCli for MockRequest {
     }
 
     fn torrent_add(&mut self, _args: TorrentAddArgs) -> Result<TorrentAdded> {
+        if self.duplicate {
+            return Ok(TorrentAdded::TorrentDuplicate {
+                id: Some(6),
+                name: Some("added.pdf".into()),
+                hash_string: Some("03a4f88adee883a3a135f10042442894af4167f7".into()),
+            });
+        }
         Ok(TorrentAdded::TorrentAdded {
             id: Some(6),
             name: Some("added.pdf".into()),
@@ -595,12 +1355,16 @@ impl TorrentCli for MockRequest {
     }
 
     fn session_get(&mut self) -> Result<SessionGet> {
+        if self.fail_connection > 0 {
+            self.fail_connection -= 1;
+            bail!(RpcConnection("connection refused".to_string()));
+        }
         Ok(SessionGet {
             blocklist_enabled: false,
             download_dir: "/mydldir".to_string(),
             encryption: "wut?".to_string(),
             peer_port: 8080,
-            rpc_version: 7,
+            rpc_version: self.rpc_version,
             rpc_version_minimum: 3,
             version: "2.0".to_string(),
         })
@@ -621,6 +1385,10 @@ impl TorrentCli for MockRequest {
         if self.fail_rpc {
             bail!("rpc request failed");
         }
+        if self.fail_connection > 0 {
+            self.fail_connection -= 1;
+            bail!(RpcConnection("connection refused".to_string()));
+        }
         Ok(self.mock_data.clone())
     }
 
@@ -631,9 +1399,65 @@ impl TorrentCli for MockRequest {
         Ok(())
     }
 
-    fn set_location(&mut self, _ids: Vec<String>, _mv: bool, _location: String) -> Result<()> {
+    fn set_location(&mut self, ids: Vec<String>, _mv: bool, location: String) -> Result<()> {
+        if self.fail_set_location {
+            bail!("rpc request failed");
+        }
+        self.locations.push((ids, location));
+        Ok(())
+    }
+
+    fn set_priority(&mut self, ids: Vec<String>, priority: PriorityLevel) -> Result<()> {
+        self.priorities.push((ids, priority));
+        Ok(())
+    }
+
+    fn set_file_priority(
+        &mut self,
+        id: String,
+        files: Vec<usize>,
+        level: PriorityLevel,
+    ) -> Result<()> {
+        self.file_priorities.push((id, files, level));
+        Ok(())
+    }
+
+    fn set_files_unwanted(&mut self, id: String, files: Vec<usize>) -> Result<()> {
+        self.files_unwanted.push((id, files));
+        Ok(())
+    }
+
+    fn set_honors_session_limits(&mut self, ids: Vec<String>, honors: bool) -> Result<()> {
+        self.session_limits.push((ids, honors));
+        Ok(())
+    }
+
+    fn set_group(&mut self, ids: Vec<String>, group: String) -> Result<()> {
+        self.groups.push((ids, group));
+        Ok(())
+    }
+
+    fn session_set_speed(&mut self, down: Option<i64>, up: Option<i64>) -> Result<()> {
+        if self.fail_rpc {
+            bail!("rpc request failed");
+        }
+        self.session_speed.push((down, up));
         Ok(())
     }
+
+    fn session_get_speed(&mut self) -> Result<(Option<i64>, Option<i64>)> {
+        let mut limits = (None, None);
+        for &(down, up) in &self.session_speed {
+            for (limit, speed) in [(&mut limits.0, down), (&mut limits.1, up)] {
+                match speed {
+                    None => {}
+                    Some(0) => *limit = None,
+                    s => *limit = s,
+                }
+            }
+        }
+        Ok(limits)
+    }
 }
 
 #[must_use]
@@ -815,9 +1639,17 @@ pub fn new_torrent() -> Torrent {
     }
 }
 
+/// A torrent date `secs` seconds after the epoch
+#[must_use]
+pub fn test_date<T: From<SystemTime>>(secs: u64) -> T {
+    (SystemTime::UNIX_EPOCH + Duration::from_secs(secs)).into()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use transmission_rpc::types::Trackers;
+
     #[test]
     fn filter_cleanable() {
         let mut tor = new_torrent();
@@ -836,6 +1668,440 @@ mod tests {
         assert_eq!(filter.filter_is_cleanable(&tor), Some(true));
     }
 
+    #[test]
+    fn filter_stalled() {
+        let builder = Config::get("tester");
+        let mut tor = new_torrent();
+        let qcmd = QueryCmd {
+            stalled: true,
+            ..QueryCmd::default()
+        };
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.torrent_filter(&tor), None);
+        tor.is_stalled = Some(false);
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        tor.is_stalled = Some(true);
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+    }
+
+    #[test]
+    fn filter_cleanable_ratio() {
+        let mut tor = new_torrent();
+        tor.is_finished = Some(true);
+        tor.status = Some(TorrentStatus::Stopped);
+        tor.download_dir = Some("/hello".to_string());
+        let builder = Config::get("tester");
+        let qcmd = QueryCmd {
+            ratio: Some(2.0),
+            ..QueryCmd::default()
+        };
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_ne!(filter.filter_is_cleanable(&tor), Some(true));
+        tor.upload_ratio = Some(1.5);
+        assert_eq!(filter.filter_is_cleanable(&tor), Some(false));
+        tor.upload_ratio = Some(-1.0);
+        assert_eq!(filter.filter_is_cleanable(&tor), Some(false));
+        tor.upload_ratio = Some(2.0);
+        assert_eq!(filter.filter_is_cleanable(&tor), Some(true));
+        tor.upload_ratio = Some(-2.0);
+        assert_eq!(filter.filter_is_cleanable(&tor), Some(true));
+    }
+
+    #[test]
+    fn filter_seeding_downloading() {
+        let builder = Config::get("tester");
+        let mut tor = new_torrent();
+        tor.status = Some(TorrentStatus::QueuedToSeed);
+        let mut qcmd = QueryCmd {
+            seeding: true,
+            ..QueryCmd::default()
+        };
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+
+        qcmd.seeding = false;
+        qcmd.downloading = true;
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+
+        qcmd.status.push(MyTorrentStatus::QueuedToSeed);
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+        tor.status = Some(TorrentStatus::Downloading);
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+        tor.status = Some(TorrentStatus::Stopped);
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+    }
+
+    #[test]
+    fn sort_ties_by_id() {
+        let mut torrents: Vec<Torrent> = [5, 2, 9, 1]
+            .into_iter()
+            .map(|id| test_torrent(id, "same"))
+            .collect();
+        for sort in [Sort::Name, Sort::Size, Sort::Urate, Sort::Age] {
+            for reverse in [false, true, false] {
+                Client::<MockRequest>::sort(&mut torrents, sort, reverse);
+                let ids: Vec<Option<i64>> = torrents.iter().map(|t| t.id).collect();
+                assert_eq!(ids, [Some(1), Some(2), Some(5), Some(9)]);
+            }
+        }
+        Client::<MockRequest>::sort(&mut torrents, Sort::Id, true);
+        assert_eq!(torrents[0].id, Some(9));
+    }
+
+    #[test]
+    fn wait_for_daemon() {
+        let mut client = Config::get_mock().new_client().unwrap();
+        client.imp.fail_connection = 2;
+        client.wait_for_daemon(Duration::from_secs(5)).unwrap();
+        assert_eq!(client.imp.fail_connection, 0);
+
+        client.imp.fail_connection = 100;
+        let err = client
+            .wait_for_daemon(Duration::from_millis(250))
+            .unwrap_err();
+        assert!(err.downcast_ref::<RpcConnection>().is_some());
+    }
+
+    #[test]
+    fn call_errors() {
+        let tokio = new_runtime().unwrap();
+        let mut client = TransClient::new("http://127.0.0.1:1/transmission/rpc".parse().unwrap());
+        let err = call(&tokio, client.session_get()).unwrap_err();
+        assert!(err.downcast_ref::<RpcConnection>().is_some());
+
+        let err = std::io::Error::new(std::io::ErrorKind::InvalidData, "bad json");
+        assert!(!is_transport_error(&err));
+    }
+
+    #[test]
+    fn sort_seed_time() {
+        let mut torrents: Vec<Torrent> = [(1, Some(10)), (2, None), (3, Some(30))]
+            .into_iter()
+            .map(|(id, secs)| {
+                let mut t = test_torrent(id, "same");
+                t.seconds_seeding = secs;
+                t
+            })
+            .collect();
+        Client::<MockRequest>::sort(&mut torrents, Sort::SeedTime, false);
+        let ids: Vec<Option<i64>> = torrents.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [Some(3), Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn default_sort() {
+        let mut client = Client {
+            imp: MockRequest::default(),
+            dldirs: vec![],
+            default_sort: Some(Sort::Id),
+            default_reverse: true,
+        };
+        let ids = |client: &mut Client<MockRequest>, qcmd: &QueryCmd| -> Vec<Option<i64>> {
+            let torrents = client.torrent_query_sort(None, qcmd).unwrap();
+            torrents.iter().map(|t| t.id).collect()
+        };
+        let mut qcmd = QueryCmd::default();
+        assert_eq!(ids(&mut client, &qcmd), [Some(3), Some(2), Some(1)]);
+        qcmd.reverse = true;
+        assert_eq!(ids(&mut client, &qcmd), [Some(1), Some(2), Some(3)]);
+        qcmd.sort = Some(Sort::Id);
+        assert_eq!(ids(&mut client, &qcmd), [Some(3), Some(2), Some(1)]);
+        qcmd.reverse = false;
+        assert_eq!(ids(&mut client, &qcmd), [Some(1), Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn read_ids() {
+        let mut qcmd = QueryCmd::default();
+        qcmd.read_ids("3\n\nABED48ADEB5E396F54A7089CBE6C1F2BC1B0DBC8\n  12 \n".as_bytes())
+            .unwrap();
+        assert_eq!(qcmd.ids, [3, 12]);
+        assert_eq!(qcmd.hsh, ["abed48adeb5e396f54a7089cbe6c1f2bc1b0dbc8"]);
+        assert!(qcmd.read_ids("testing.pdf\n".as_bytes()).is_err());
+        let err = QueryCmd::default()
+            .read_ids(" \n\n".as_bytes())
+            .unwrap_err();
+        assert!(err.downcast_ref::<NoMatches>().is_some());
+    }
+
+    #[test]
+    fn set_locations() {
+        let mut client = Config::get_mock().new_client().unwrap();
+        client
+            .set_locations(
+                vec![
+                    (vec!["a".to_string()], "/x".to_string()),
+                    (vec!["b".to_string()], "/y".to_string()),
+                    (vec!["c".to_string(), "d".to_string()], "/x".to_string()),
+                ],
+                true,
+            )
+            .unwrap();
+        assert_eq!(
+            client.imp.locations,
+            [
+                (vec!["a".into(), "c".into(), "d".into()], "/x".to_string()),
+                (vec!["b".into()], "/y".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn filter_size() {
+        let builder = Config::get("tester");
+        let mut tor = new_torrent();
+        let mut qcmd = QueryCmd {
+            min_size: Some(parse_byte("1GiB").unwrap()),
+            max_size: Some(parse_byte("3GB").unwrap()),
+            ..QueryCmd::default()
+        };
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.torrent_filter(&tor), None);
+        tor.size_when_done = Some(2 * 1024 * 1024 * 1024);
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+        tor.size_when_done = Some(3 * 1024 * 1024 * 1024);
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        tor.size_when_done = Some(1024);
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        qcmd.min_size = None;
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+    }
+
+    #[test]
+    fn filter_count_unknown() {
+        let builder = Config::get("tester");
+        let mut sized = new_torrent();
+        sized.size_when_done = Some(2 * 1024 * 1024 * 1024);
+        let torrents = [new_torrent(), sized];
+        let qcmd = QueryCmd {
+            min_size: Some(parse_byte("1GiB").unwrap()),
+            ..QueryCmd::default()
+        };
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.count_unknown(&torrents), 1);
+        let qcmd = QueryCmd::default();
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.count_unknown(&torrents), 0);
+    }
+
+    #[test]
+    fn filter_done() {
+        let builder = Config::get("tester");
+        let mut tor = new_torrent();
+        let mut qcmd = QueryCmd {
+            min_done: Some(90.0),
+            max_done: Some(99.9),
+            ..QueryCmd::default()
+        };
+        let filter = builder.new_filter(&qcmd).unwrap();
+        tor.percent_done = None;
+        assert_eq!(filter.torrent_filter(&tor), None);
+        tor.percent_done = Some(0.99);
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+        tor.percent_done = Some(1.0);
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        tor.percent_done = Some(0.5);
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        qcmd.min_done = None;
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+    }
+
+    #[test]
+    fn filter_active() {
+        let builder = Config::get("tester");
+        let mut tor = new_torrent();
+        let qcmd = QueryCmd {
+            active: true,
+            ..QueryCmd::default()
+        };
+        let filter = builder.new_filter(&qcmd).unwrap();
+        tor.rate_download = None;
+        tor.rate_upload = Some(0);
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        tor.rate_upload = Some(10);
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+        tor.rate_upload = None;
+        tor.rate_download = Some(10);
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+    }
+
+    #[test]
+    fn filter_peers() {
+        let builder = Config::get("tester");
+        let mut tor = new_torrent();
+        let mut qcmd = QueryCmd {
+            max_peers: Some(0),
+            ..QueryCmd::default()
+        };
+        let filter = builder.new_filter(&qcmd).unwrap();
+        tor.peers_connected = None;
+        assert_eq!(filter.torrent_filter(&tor), None);
+        tor.peers_connected = Some(0);
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+        tor.peers_connected = Some(3);
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        qcmd.max_peers = None;
+        qcmd.min_peers = Some(3);
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+    }
+
+    #[test]
+    fn filter_eta() {
+        let builder = Config::get("tester");
+        let mut tor = new_torrent();
+        let mut qcmd = QueryCmd {
+            max_eta: Some(parse_eta("1h").unwrap()),
+            ..QueryCmd::default()
+        };
+        assert_eq!(qcmd.max_eta, Some(3600));
+        let filter = builder.new_filter(&qcmd).unwrap();
+        tor.eta = None;
+        assert_eq!(filter.torrent_filter(&tor), None);
+        tor.eta = Some(600);
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+        tor.eta = Some(7200);
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        tor.eta = Some(-1);
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        qcmd.max_eta = None;
+        qcmd.min_eta = Some(parse_eta("900").unwrap());
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        tor.eta = Some(7200);
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+        assert!(parse_eta("soon").is_err());
+    }
+
+    #[test]
+    fn filter_completed_before() {
+        let builder = Config::get("tester");
+        let mut tor = new_torrent();
+        let qcmd = QueryCmd {
+            completed_before: Some(parse_time("2024-01-02").unwrap()),
+            ..QueryCmd::default()
+        };
+        assert_eq!(qcmd.completed_before, Some(1_704_153_600));
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        tor.done_date = Some(test_date(0));
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        tor.done_date = Some(test_date(1_704_153_599));
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+        tor.done_date = Some(test_date(1_704_153_600));
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        assert!(parse_time("2w").unwrap() > 1_704_153_600);
+        assert!(parse_time("yesterday").is_err());
+    }
+
+    #[test]
+    fn filter_active_since() {
+        let builder = Config::get("tester");
+        let mut tor = new_torrent();
+        let qcmd = QueryCmd {
+            active_since: Some(parse_time("1700000000").unwrap()),
+            ..QueryCmd::default()
+        };
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        tor.activity_date = Some(test_date(0));
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        tor.activity_date = Some(test_date(1_699_999_999));
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        tor.activity_date = Some(test_date(1_700_000_000));
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+    }
+
+    #[test]
+    fn filter_labels() {
+        let builder = Config::get("tester");
+        let mut tor = new_torrent();
+        let qcmd = QueryCmd {
+            labels: vec!["auto".to_string(), "tv".to_string()],
+            ..QueryCmd::default()
+        };
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.torrent_filter(&tor), None);
+        tor.labels = Some(vec!["auto".to_string()]);
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+        tor.labels = Some(vec!["tv".to_string(), "auto".to_string()]);
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+    }
+
+    #[test]
+    fn smart_ids() {
+        let mut qcmd = QueryCmd {
+            strs: vec![
+                "12".to_string(),
+                "ABED48ADEB5E396F54A7089CBE6C1F2BC1B0DBC8".to_string(),
+                "name".to_string(),
+                "abed48".to_string(),
+            ],
+            ..QueryCmd::default()
+        };
+        qcmd.apply_smart_ids();
+        assert_eq!(qcmd.strs.len(), 4);
+        qcmd.smart_ids = true;
+        qcmd.apply_smart_ids();
+        assert_eq!(qcmd.strs, ["name", "abed48"]);
+        assert_eq!(qcmd.ids, [12]);
+        assert_eq!(qcmd.hsh, ["abed48adeb5e396f54a7089cbe6c1f2bc1b0dbc8"]);
+    }
+
+    fn tracker(id: i32, announce: &str) -> Trackers {
+        Trackers {
+            id,
+            announce: announce.to_string(),
+            scrape: String::new(),
+            sitename: String::new(),
+            tier: 0,
+        }
+    }
+
+    #[test]
+    fn filter_trackers() {
+        let builder = Config::get("tester");
+        let mut tor = new_torrent();
+        tor.trackers = Some(vec![
+            tracker(0, "http://tracker.example.org/announce"),
+            tracker(1, "udp://open.example.net:1337"),
+        ]);
+        let mut qcmd = QueryCmd {
+            trackers: vec!["example.org".to_string(), "open.example".to_string()],
+            ..QueryCmd::default()
+        };
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+
+        qcmd.trackers.push("missing".to_string());
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+    }
+
+    #[test]
+    fn filter_exclude_trackers() {
+        let builder = Config::get("tester");
+        let mut tor = new_torrent();
+        tor.trackers = Some(vec![tracker(0, "http://tracker.Private.org/announce")]);
+        let mut qcmd = QueryCmd {
+            exclude_trackers: vec!["private.org".to_string()],
+            ..QueryCmd::default()
+        };
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.torrent_filter(&tor), Some(false));
+
+        tor.trackers = Some(vec![]);
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+
+        qcmd.exclude_trackers = vec!["other".to_string()];
+        let filter = builder.new_filter(&qcmd).unwrap();
+        assert_eq!(filter.torrent_filter(&tor), Some(true));
+    }
+
     #[test]
     fn filter_nones() {
         let builder = Config::get("tester");
@@ -1,4 +1,7 @@
-use crate::client::{Client, MockRequest, QueryCmd, SyncRequest, TorrentCli, TorrentFilter};
+use crate::client::{
+    new_runtime, Client, HashColumn, MockRequest, QueryCmd, Sort, SyncRequest, TorrentCli,
+    TorrentFilter,
+};
 #[cfg(test)]
 use crate::console::imps::tests::{MockCon, MockReader, MockView};
 use crate::console::{
@@ -8,22 +11,26 @@ use crate::db::DBSqlite;
 use crate::errors::*;
 use crate::{Trctl, Trmv};
 use byte_unit::Byte;
+use notify_rust::Urgency;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use termcolor::WriteColor;
 use toml::Value;
 use transmission_rpc::types::BasicAuth;
 use transmission_rpc::TransClient;
-use url::Url;
+use url::{Host, Url};
 
 pub fn option_explicit_none<'de, T, D>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
 where
     D: Deserializer<'de>,
     T: Deserialize<'de>,
 {
-    Ok(match Value::deserialize(deserializer)? {
-        Value::String(ref value) if value.to_lowercase() == "none" => None,
-        value => Some(T::deserialize(value).map_err(serde::de::Error::custom)?),
+    // json has null in addition to "none"
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        None => None,
+        Some(Value::String(ref value)) if value.to_lowercase() == "none" => None,
+        Some(value) => Some(T::deserialize(value).map_err(serde::de::Error::custom)?),
     })
 }
 
@@ -48,6 +55,8 @@ pub struct Config {
     #[serde(serialize_with = "option_explicit_serialize")]
     #[serde(deserialize_with = "option_explicit_none")]
     pub mailuser: Option<String>,
+    /// Full url of the rpc endpoint including the path, e.g. `https://host/tr/rpc` when behind
+    /// a reverse proxy. The path is passed as is to the daemon.
     pub rpc_url: Url,
     #[serde(serialize_with = "option_explicit_serialize")]
     #[serde(deserialize_with = "option_explicit_none")]
@@ -64,16 +73,180 @@ pub struct Config {
     #[serde(serialize_with = "option_explicit_serialize")]
     #[serde(deserialize_with = "option_explicit_none")]
     pub copydir: Option<PathBuf>,
+    pub copydir_name: CopydirName,
+    /// Labels set on every added torrent
+    pub add_labels: Vec<String>,
     pub quota_per_dldir: Byte,
+    /// Refuse to add torrents that would take a dldir over its quota instead of adding them
+    /// paused
+    pub strict_quota: bool,
     pub free_space_per_dldir: Byte,
     pub dst_free_space_to_leave: Byte,
     #[serde(serialize_with = "option_explicit_serialize")]
     #[serde(deserialize_with = "option_explicit_none")]
     pub color: Option<bool>,
+    pub notify: bool,
+    pub prune_empty_dirs: bool,
+    /// Remove the hash dir on rm also for remote torrents. For when the remote storage is
+    /// mounted at the same path locally.
+    pub remote_rmdir: bool,
+    /// Remove a symlinked hash dir on rm by removing the link and leaving the target. Otherwise
+    /// such dirs are refused.
+    pub follow_symlinks_on_rm: bool,
+    /// Remove the hash dir on rm even if transmission left files in it
+    pub rmdir_non_empty: bool,
+    /// Ask to type the count when rm selects more torrents than this, unless --yes is given
+    pub rm_confirm_threshold: usize,
+    /// Always match query strings that look like hashes or ids as such
+    pub smart_ids: bool,
+    /// Sort of the queries that don't give one
+    #[serde(serialize_with = "option_explicit_serialize")]
+    #[serde(deserialize_with = "option_explicit_none")]
+    pub default_sort: Option<Sort>,
+    /// Reverse the default sort. --reverse flips it back.
+    pub default_reverse: bool,
+    /// Color the ratios at least this green. Off by default
+    #[serde(serialize_with = "option_explicit_serialize")]
+    #[serde(deserialize_with = "option_explicit_none")]
+    pub ratio_good: Option<f32>,
+    /// Color the ratios below this red. Off by default
+    #[serde(serialize_with = "option_explicit_serialize")]
+    #[serde(deserialize_with = "option_explicit_none")]
+    pub ratio_bad: Option<f32>,
+    /// Icon of the desktop notifications. Defaults to `/usr/share/pixmaps/<name>.png`
+    #[serde(serialize_with = "option_explicit_serialize")]
+    #[serde(deserialize_with = "option_explicit_none")]
+    pub notify_icon: Option<PathBuf>,
+    /// Default of --existing on add, i.e. expect the data of the added torrents to be in the
+    /// dldir already
+    pub default_use_existing: bool,
+    /// Allow the hidden rpc subcommand that sends raw requests. Neither the requests nor the
+    /// responses are validated.
+    pub allow_raw_rpc: bool,
+    pub notify_urgency: NotifyLevels,
+    /// Dldir of the added torrents whose tracker url contains the key, when no --dldir is given
+    pub tracker_dldir: BTreeMap<String, PathBuf>,
+    /// Headers of the torrent file downloads by host, e.g. `"example.org" = ["Cookie: uid=1"]`
+    pub http_headers: BTreeMap<String, Vec<String>>,
+    pub prompts: Prompts,
 }
 
 pub type Def = Builder<SyncRequest>;
 
+/// Set to add without looking up or recording earlier adds
+pub const NO_DEDUPE_ENV: &str = "TRCTL_NO_DEDUPE";
+
+/// How added torrent files are named in the copydir
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CopydirName {
+    /// `<hash>.torrent` as saved by the move script. Adding does not save a copy.
+    Hash,
+    /// Adding saves `<name>.torrent` with a `.hash/<hash>.torrent` link for looking it up
+    Name,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyUrgency {
+    Low,
+    Normal,
+    Critical,
+}
+
+impl From<NotifyUrgency> for Urgency {
+    fn from(urgency: NotifyUrgency) -> Self {
+        match urgency {
+            NotifyUrgency::Low => Urgency::Low,
+            NotifyUrgency::Normal => Urgency::Normal,
+            NotifyUrgency::Critical => Urgency::Critical,
+        }
+    }
+}
+
+/// Urgency of the notifications sent for the messages of each log level. Levels set to none
+/// are only logged.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct NotifyLevels {
+    #[serde(serialize_with = "option_explicit_serialize")]
+    #[serde(deserialize_with = "option_explicit_none")]
+    pub error: Option<NotifyUrgency>,
+    #[serde(serialize_with = "option_explicit_serialize")]
+    #[serde(deserialize_with = "option_explicit_none")]
+    pub warn: Option<NotifyUrgency>,
+    #[serde(serialize_with = "option_explicit_serialize")]
+    #[serde(deserialize_with = "option_explicit_none")]
+    pub info: Option<NotifyUrgency>,
+}
+
+impl Default for NotifyLevels {
+    fn default() -> Self {
+        Self {
+            error: Some(NotifyUrgency::Critical),
+            warn: None,
+            info: None,
+        }
+    }
+}
+
+impl NotifyLevels {
+    #[must_use]
+    pub fn get(&self, level: log::Level) -> Option<Urgency> {
+        match level {
+            log::Level::Error => self.error,
+            log::Level::Warn => self.warn,
+            log::Level::Info => self.info,
+            log::Level::Debug | log::Level::Trace => None,
+        }
+        .map(Urgency::from)
+    }
+}
+
+/// Texts of the interactive prompts. The answers are accepted in addition to `y`, `n` and `a`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Prompts {
+    pub select: String,
+    pub yes: String,
+    pub no: String,
+    pub all: String,
+}
+
+impl Default for Prompts {
+    fn default() -> Self {
+        Self {
+            select: "Select".to_string(),
+            yes: "y".to_string(),
+            no: "n".to_string(),
+            all: "a".to_string(),
+        }
+    }
+}
+
+impl Prompts {
+    fn is(ans: &str, builtin: &str, configured: &str) -> bool {
+        ans.eq_ignore_ascii_case(builtin)
+            || (!configured.is_empty() && ans.to_lowercase() == configured.to_lowercase())
+    }
+
+    #[must_use]
+    pub fn is_yes(&self, ans: &str) -> bool {
+        Self::is(ans, "y", &self.yes)
+    }
+
+    /// An empty answer is a no
+    #[must_use]
+    pub fn is_no(&self, ans: &str) -> bool {
+        ans.is_empty() || Self::is(ans, "n", &self.no)
+    }
+
+    #[must_use]
+    pub fn is_all(&self, ans: &str) -> bool {
+        Self::is(ans, "a", &self.all)
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -93,9 +266,30 @@ impl Default for Config {
                 .collect(),
             ask_existing: true,
             copydir: None,
+            copydir_name: CopydirName::Hash,
+            add_labels: Vec::new(),
             quota_per_dldir: Byte::parse_str("100GiB", true).expect("Invalid byte"),
+            strict_quota: false,
             free_space_per_dldir: Byte::parse_str("100GiB", true).expect("Invalid byte"),
             dst_free_space_to_leave: Byte::parse_str("40GiB", true).expect("Invalid byte"),
+            notify: false,
+            prune_empty_dirs: false,
+            remote_rmdir: false,
+            follow_symlinks_on_rm: false,
+            rmdir_non_empty: false,
+            rm_confirm_threshold: 20,
+            smart_ids: false,
+            default_sort: None,
+            default_reverse: false,
+            notify_icon: None,
+            default_use_existing: false,
+            allow_raw_rpc: false,
+            notify_urgency: NotifyLevels::default(),
+            tracker_dldir: BTreeMap::new(),
+            http_headers: BTreeMap::new(),
+            prompts: Prompts::default(),
+            ratio_good: None,
+            ratio_bad: None,
         }
     }
 }
@@ -105,14 +299,51 @@ impl Config {
         confy::load(name, Some("config")).context("Config")
     }
 
+    /// A `.json` config is parsed as json, anything else as toml. A missing toml config is
+    /// created with the defaults.
     pub fn load_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if Self::is_json(path) {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("read {}", path.display()))?;
+            return serde_json::from_str(&content).context("Config");
+        }
         confy::load_path(path).context("Config")
     }
 
+    fn is_json(path: &Path) -> bool {
+        path.extension().is_some_and(|ext| ext == "json")
+    }
+
     pub fn config_path(name: &str) -> Result<PathBuf> {
         confy::get_configuration_file_path(name, Some("config")).context("config path")
     }
 
+    /// The config with every field written out
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string_pretty(self).context("serialize config")
+    }
+
+    /// Write the default config to `path`
+    pub fn init_path(path: &Path, force: bool) -> Result<()> {
+        if path.exists() && !force {
+            bail!(
+                "{} already exists, use --force to overwrite",
+                path.display()
+            );
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("create {}", parent.display()))?;
+        }
+        let content = if Self::is_json(path) {
+            serde_json::to_string_pretty(&Self::default()).context("serialize config")?
+        } else {
+            Self::default().to_toml()?
+        };
+        std::fs::write(path, content).with_context(|| format!("write {}", path.display()))
+    }
+
     pub fn builder(self, name: &str) -> Def {
         self.builder_with(Builder::default_client, name.to_string())
     }
@@ -126,6 +357,8 @@ impl Config {
             cfg: self,
             fclient,
             interactive: true,
+            pick: false,
+            show_hash: None,
             name,
         }
     }
@@ -136,6 +369,8 @@ impl Config {
             cfg: Config::default(),
             fclient: Builder::default_client,
             interactive: true,
+            pick: false,
+            show_hash: None,
             name: name.to_string(),
         }
     }
@@ -146,6 +381,8 @@ impl Config {
             cfg: Config::default(),
             fclient: Builder::mock_client,
             interactive: true,
+            pick: false,
+            show_hash: None,
             name: "mockman".to_string(),
         }
     }
@@ -155,6 +392,8 @@ pub struct Builder<C> {
     pub cfg: Config,
     fclient: fn(&Self) -> Result<C>,
     pub interactive: bool,
+    pub pick: bool,
+    pub show_hash: Option<HashColumn>,
     name: String,
 }
 
@@ -166,6 +405,26 @@ impl Builder<MockRequest> {
         Ok(MockView::default())
     }
 
+    pub fn mock_trmv(mut self, log: StdLog<Buffer>) -> Result<Trmv<MockRequest, MockCon>> {
+        // never the real db
+        self.cfg.sqlitedb = false;
+        let view = Console {
+            v_ask_existing: true,
+            ratio_good: self.cfg.ratio_good,
+            ratio_bad: self.cfg.ratio_bad,
+            pick: false,
+            show_hash: None,
+            prompts: self.cfg.prompts.clone(),
+            base_dir: self.cfg.base_dir.clone(),
+            log,
+            input: MockReader {
+                input: String::new(),
+                input_pos: 0,
+            },
+        };
+        self.new_trmv_view(view)
+    }
+
     pub fn mock_trctl(self, log: StdLog<Buffer>) -> Result<Trctl<MockRequest, MockCon>> {
         let client = self.new_client()?;
         Ok(Trctl {
@@ -173,10 +432,23 @@ impl Builder<MockRequest> {
             dst_free_space_to_leave: 10,
             interactive: true,
             is_remote: false,
+            notifier: None,
             client,
             verify: self.cfg.verify,
+            prune_empty_dirs: self.cfg.prune_empty_dirs,
+            remote_rmdir: self.cfg.remote_rmdir,
+            follow_symlinks_on_rm: self.cfg.follow_symlinks_on_rm,
+            rmdir_non_empty: self.cfg.rmdir_non_empty,
+            rm_confirm_threshold: self.cfg.rm_confirm_threshold,
+            #[cfg(feature = "sqlite")]
+            db: DBSqlite::new(None),
             console: Console {
                 v_ask_existing: true,
+                ratio_good: self.cfg.ratio_good,
+                ratio_bad: self.cfg.ratio_bad,
+                pick: false,
+                show_hash: None,
+                prompts: self.cfg.prompts,
                 base_dir: self.cfg.base_dir,
                 log,
                 input: MockReader {
@@ -191,13 +463,23 @@ impl Builder<MockRequest> {
 #[derive(Clone, Copy)]
 pub struct BuilderOpts {
     pub interactive: bool,
+    pub pick: bool,
+    pub show_hash: Option<HashColumn>,
 }
 
 impl<C: TorrentCli> Builder<C> {
     #[allow(clippy::unused_self)]
     #[must_use]
     pub fn new_notifier_dbus(&self, name: String) -> Notifier<Dbus> {
-        Notifier::new(Dbus::new(name.clone(), self.cfg.ask_existing), name)
+        Notifier::new(
+            Dbus::new(
+                name.clone(),
+                self.cfg.ask_existing,
+                self.cfg.notify_icon.as_deref(),
+            ),
+            name,
+            self.cfg.notify_urgency,
+        )
     }
 
     #[cfg(feature = "sqlite")]
@@ -208,6 +490,8 @@ impl<C: TorrentCli> Builder<C> {
 
     pub fn set_cli_opts(&mut self, opts: BuilderOpts) {
         self.interactive = opts.interactive;
+        self.pick = opts.pick;
+        self.show_hash = opts.show_hash;
     }
 
     pub fn new_notifier_email(&self, name: String) -> Result<Notifier<Unprivileged>> {
@@ -215,16 +499,18 @@ impl<C: TorrentCli> Builder<C> {
         Ok(Notifier::new(
             Unprivileged::new(&touser, name.clone())?,
             name,
+            self.cfg.notify_urgency,
         ))
     }
 
     pub fn default_client(&self) -> Result<SyncRequest> {
         let client = self.new_transmission()?;
-        let tokio = tokio::runtime::Builder::new_current_thread()
-            .enable_io()
-            .enable_time()
-            .build()?;
-        Ok(SyncRequest { client, tokio })
+        Ok(SyncRequest {
+            client,
+            tokio: new_runtime()?,
+            url: self.cfg.rpc_url.clone(),
+            auth: self.cfg.rpc_user.clone().zip(self.cfg.rpc_pass.clone()),
+        })
     }
 
     pub fn new_filter<'h>(&'h self, qcmd: &'h QueryCmd) -> Result<TorrentFilter<'h>> {
@@ -240,20 +526,23 @@ impl<C: TorrentCli> Builder<C> {
         Ok(Client {
             imp: (self.fclient)(self)?,
             dldirs: self.cfg.dldirs.clone(),
+            default_sort: self.cfg.default_sort,
+            default_reverse: self.cfg.default_reverse,
         })
     }
 
+    // Only the host matters. An ssh forwarded port on localhost is local as far as we can tell
+    // so force_not_remote has to be used if it is not.
     fn is_remote(url: &Url, force_not_remote: bool) -> bool {
         if force_not_remote {
             return false;
         }
-        let host = url.host_str();
-        if let Some(h) = host {
-            if h.starts_with("127.") || h == "localhost" || h == "::1" {
-                return false;
-            }
+        match url.host() {
+            Some(Host::Ipv4(ip)) => !ip.is_loopback(),
+            Some(Host::Ipv6(ip)) => !ip.is_loopback(),
+            Some(Host::Domain(h)) => h != "localhost",
+            None => true,
         }
-        true
     }
 
     pub fn new_trctl_input<IO: WriteColor, I: ReadLine>(
@@ -262,14 +551,42 @@ impl<C: TorrentCli> Builder<C> {
         input: I,
     ) -> std::result::Result<Trctl<C, Console<IO, I>>, Error> {
         let client = self.new_client()?;
+        let notifier = if self.cfg.notify {
+            Some(Dbus::new(
+                self.name.clone(),
+                self.cfg.ask_existing,
+                self.cfg.notify_icon.as_deref(),
+            ))
+        } else {
+            None
+        };
+        #[cfg(feature = "sqlite")]
+        let db = DBSqlite::new(if self.cfg.sqlitedb {
+            Some(self.sqlitedbpath()?)
+        } else {
+            None
+        });
         Ok(Trctl {
+            #[cfg(feature = "sqlite")]
+            db,
             interactive: self.interactive,
             verify: self.cfg.verify,
+            prune_empty_dirs: self.cfg.prune_empty_dirs,
+            remote_rmdir: self.cfg.remote_rmdir,
+            follow_symlinks_on_rm: self.cfg.follow_symlinks_on_rm,
+            rmdir_non_empty: self.cfg.rmdir_non_empty,
+            rm_confirm_threshold: self.cfg.rm_confirm_threshold,
             dldirs: self.cfg.dldirs,
             is_remote: Self::is_remote(&self.cfg.rpc_url, self.cfg.force_not_remote),
             dst_free_space_to_leave: self.cfg.dst_free_space_to_leave.as_u64(),
+            notifier,
             console: Console {
                 v_ask_existing: self.cfg.ask_existing,
+                ratio_good: self.cfg.ratio_good,
+                ratio_bad: self.cfg.ratio_bad,
+                pick: self.pick,
+                show_hash: self.show_hash,
+                prompts: self.cfg.prompts,
                 base_dir: self.cfg.base_dir,
                 log,
                 input,
@@ -303,13 +620,19 @@ impl<C: TorrentCli> Builder<C> {
             base_dir: self.cfg.base_dir.clone(),
             input: std::io::stdin(),
             v_ask_existing: self.cfg.ask_existing,
+            ratio_good: self.cfg.ratio_good,
+            ratio_bad: self.cfg.ratio_bad,
+            pick: false,
+            show_hash: None,
+            prompts: self.cfg.prompts.clone(),
         };
         self.new_trmv_view(v)
     }
 
     pub fn new_trmv_view<V: View>(self, view: V) -> std::result::Result<Trmv<C, V>, Error> {
+        let dedupe = std::env::var_os(NO_DEDUPE_ENV).is_none();
         #[cfg(feature = "sqlite")]
-        let db = DBSqlite::new(if self.cfg.sqlitedb {
+        let db = DBSqlite::new(if self.cfg.sqlitedb && dedupe {
             Some(self.sqlitedbpath()?)
         } else {
             None
@@ -318,12 +641,21 @@ impl<C: TorrentCli> Builder<C> {
             client: self.new_client()?,
             view,
             copydir: self.cfg.copydir,
+            copydir_name: self.cfg.copydir_name,
+            add_labels: self.cfg.add_labels,
             base_dir: self.cfg.base_dir,
             dldirs: self.cfg.dldirs,
+            tracker_dldir: self.cfg.tracker_dldir,
+            default_use_existing: self.cfg.default_use_existing,
+            http_headers: self.cfg.http_headers,
             quota: self.cfg.quota_per_dldir.as_u64(),
+            strict_quota: self.cfg.strict_quota,
             safe_space: self.cfg.free_space_per_dldir.as_u64(),
             #[cfg(feature = "sqlite")]
             db,
+            dedupe,
+            projected: Vec::new(),
+            space_cache: BTreeMap::new(),
         })
     }
 
@@ -349,3 +681,96 @@ impl<C: TorrentCli> Builder<C> {
 //    //    }
 //    //    self.console.as_mut().unwrap()
 //    //}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rpc_url_path() {
+        let cfg: Config = toml::from_str(
+            r#"
+            rpc_url = "https://example.com/tr/rpc"
+            rpc_user = "user"
+            rpc_pass = "pass"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(cfg.rpc_url.as_str(), "https://example.com/tr/rpc");
+        assert!(cfg.builder("tester").new_transmission().is_ok());
+    }
+
+    #[test]
+    fn init_and_dump() {
+        let dump = Config::default().to_toml().unwrap();
+        let cfg: Config = toml::from_str(&dump).unwrap();
+        assert_eq!(cfg.to_toml().unwrap(), dump);
+
+        let dir = std::env::temp_dir().join(format!("trctl-config-{}", std::process::id()));
+        let path = dir.join("config.toml");
+        Config::init_path(&path, false).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), dump);
+        assert!(Config::init_path(&path, false).is_err());
+        Config::init_path(&path, true).unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn json_config() {
+        let cfg: Config = serde_json::from_str(
+            r#"{
+                "rpc_url": "https://example.com/tr/rpc",
+                "rpc_user": null,
+                "ratio_good": 2.0,
+                "ratio_bad": "none",
+                "notify_urgency": { "error": null, "warn": "low" }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(cfg.rpc_url.as_str(), "https://example.com/tr/rpc");
+        assert_eq!(cfg.rpc_user, None);
+        assert_eq!(cfg.ratio_good, Some(2.0));
+        assert_eq!(cfg.ratio_bad, None);
+        assert_eq!(cfg.notify_urgency.get(log::Level::Error), None);
+        assert_eq!(cfg.notify_urgency.get(log::Level::Warn), Some(Urgency::Low));
+
+        let dir = std::env::temp_dir().join(format!("trctl-json-{}", std::process::id()));
+        let path = dir.join("config.json");
+        Config::init_path(&path, false).unwrap();
+        let cfg = Config::load_path(&path).unwrap();
+        assert_eq!(cfg.to_toml().unwrap(), Config::default().to_toml().unwrap());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn notify_urgency() {
+        let cfg: Config = toml::from_str(
+            r#"
+            [notify_urgency]
+            error = "none"
+            warn = "low"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(cfg.notify_urgency.get(log::Level::Error), None);
+        assert_eq!(cfg.notify_urgency.get(log::Level::Warn), Some(Urgency::Low));
+        assert_eq!(cfg.notify_urgency.get(log::Level::Info), None);
+        let levels = NotifyLevels::default();
+        assert_eq!(levels.get(log::Level::Error), Some(Urgency::Critical));
+        assert_eq!(levels.get(log::Level::Warn), None);
+    }
+
+    #[test]
+    fn is_remote() {
+        let remote = |url: &str| Def::is_remote(&Url::parse(url).unwrap(), false);
+        assert!(!remote("http://127.0.0.1:9091/transmission/rpc"));
+        assert!(!remote("http://localhost:19091/tr/rpc"));
+        assert!(!remote("http://[::1]:9091/transmission/rpc"));
+        assert!(remote("https://example.com/tr/rpc"));
+        assert!(remote("http://192.168.1.2:9091/transmission/rpc"));
+        assert!(!Def::is_remote(
+            &Url::parse("https://example.com/tr/rpc").unwrap(),
+            true
+        ));
+    }
+}
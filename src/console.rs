@@ -1,14 +1,23 @@
 #![allow(clippy::module_name_repetitions)]
 
-use crate::client::{TorrentAction, TorrentAdded};
-use crate::display::{ByteSize, Torrent as DisplayTorrent};
+use crate::client::{
+    HashColumn, MyTorrentStatus, OutputFormat, PriorityLevel, TorrentAction, TorrentAdded,
+};
+use crate::config::{NotifyLevels, Prompts};
+use crate::display::{ByteSize, Maybe, Torrent as DisplayTorrent};
 use crate::errors::*;
-use crate::TorrentAddResult;
+use crate::escape;
+use crate::template::Template;
+use crate::{DirSpace, TorrentAddResult};
+use clap::ValueEnum as _;
 use notify_rust::{Hint, Notification, Timeout, Urgency};
+use serde::Serialize;
 use std::borrow::Borrow;
+use std::collections::BTreeMap;
 use std::fmt;
-use std::io::{stdin, BufWriter, Stderr, Stdin, Stdout, Write};
-use std::path::PathBuf;
+use std::io::{stdin, BufWriter, IsTerminal as _, Stderr, Stdin, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::time::{Duration, SystemTime};
 use termcolor::{BufferedStandardStream, Color, ColorChoice, ColorSpec, WriteColor};
 use time::{macros::format_description, OffsetDateTime};
@@ -128,6 +137,9 @@ pub mod imps {
     }
 }
 
+/// Looked up from PATH
+const SENDMAIL: &str = "sendmail";
+
 pub struct Unprivileged {
     pub hostname: String,
     pub from: String,
@@ -172,13 +184,16 @@ impl NotifyView for Unprivileged {
             .subject(format!("{prio}: {subject}"))
             .body(String::from(msg.unwrap_or("<nomsg>")))?;
 
-        let mailer = SendmailTransport::new();
-        mailer.send(&email)?;
+        let mailer = SendmailTransport::new_with_command(SENDMAIL);
+        mailer
+            .send(&email)
+            .with_context(|| format!("{SENDMAIL} (from PATH) to {} failed", self.to))?;
         Ok(())
     }
 
+    // There is no one to answer an email in time
     fn ask_retry(&mut self, _err: &anyhow::Error) -> Result<bool> {
-        unimplemented!();
+        Ok(false)
     }
 
     fn ask_existing(&mut self, torrent: &[u8], modified: u64) -> Result<bool> {
@@ -196,6 +211,7 @@ impl NotifyView for Unprivileged {
     }
 }
 
+#[derive(Debug)]
 pub struct Dbus {
     pub name: String,
     pub icon: String,
@@ -206,7 +222,7 @@ pub struct Dbus {
 impl Dbus {
     #[must_use]
     #[allow(clippy::missing_panics_doc)]
-    pub fn new(name: String, v_ask_existing: bool) -> Self {
+    pub fn new(name: String, v_ask_existing: bool, icon: Option<&Path>) -> Self {
         // BUG To avoid  https://gitlab.gnome.org/GNOME/libnotify/-/issues/41
         let app_name = std::env::current_exe()
             .unwrap()
@@ -218,7 +234,10 @@ impl Dbus {
         let app_name_override = format!("{app_name}-notify");
         Self {
             // awesome wm needs absolute path or it looks the file in home dir first
-            icon: format!("/usr/share/pixmaps/{name}.png"),
+            icon: icon.map_or_else(
+                || format!("/usr/share/pixmaps/{name}.png"),
+                |icon| icon.to_string_lossy().into_owned(),
+            ),
             name,
             v_ask_existing,
             app_name_override,
@@ -305,34 +324,34 @@ pub struct Notifier<NV: NotifyView> {
     pub out: BufWriter<Stdout>,
     pub err: BufWriter<Stderr>,
     pub name: String,
+    pub urgency: NotifyLevels,
 }
 
 impl<NV: NotifyView> Notifier<NV> {
-    pub fn new(notify_view: NV, name: String) -> Self {
+    pub fn new(notify_view: NV, name: String, urgency: NotifyLevels) -> Self {
         Self {
             notify_view,
             out: BufWriter::new(std::io::stdout()),
             err: BufWriter::new(std::io::stderr()),
             name,
+            urgency,
         }
     }
 
     fn do_log(&mut self, args: fmt::Arguments, level: log::Level, send: bool) -> Result<()> {
         let (prefix, summary) = match level {
-            log::Level::Debug | log::Level::Trace => ("7", None),
-            log::Level::Info => ("6", None),
-            log::Level::Warn => ("4", None),
-            log::Level::Error => ("3", Some("Error")),
+            log::Level::Debug | log::Level::Trace => ("7", "Debug"),
+            log::Level::Info => ("6", "Info"),
+            log::Level::Warn => ("4", "Warning"),
+            log::Level::Error => ("3", "Error"),
         };
         writeln!(self.out, "<{}>{}: {}", prefix, self.name, args)?;
         self.out.flush()?;
         if send {
-            if let Some(summary) = summary {
-                return self.notify_view.notify(
-                    Urgency::Critical,
-                    summary,
-                    Some(&format!("{args}")),
-                );
+            if let Some(urgency) = self.urgency.get(level) {
+                return self
+                    .notify_view
+                    .notify(urgency, summary, Some(&format!("{args}")));
             }
         }
         Ok(())
@@ -350,6 +369,12 @@ impl<NV: NotifyView> View for Notifier<NV> {
         self.notify_view.ask_existing(torrent, modified)
     }
 
+    fn print_duplicate_id(&mut self, id: i64) -> Result<()> {
+        writeln!(self.out, "{id}")?;
+        self.out.flush()?;
+        Ok(())
+    }
+
     fn torrent_action_ok<IT>(&mut self, _torrents: IT, _action: Action) -> Result<()>
     where
         IT: IntoIterator,
@@ -413,8 +438,11 @@ impl<NV: NotifyView> Logger for Notifier<NV> {
                 } else {
                     self.do_log(format_args!("{err:#}"), log::Level::Error, false)
                         .context("log")?;
+                    let Some(urgency) = self.urgency.get(log::Level::Error) else {
+                        return Ok(());
+                    };
                     self.notify_view
-                        .notify(Urgency::Critical, "error", Some(&format!("{err:#}")))
+                        .notify(urgency, "error", Some(&format!("{err:#}")))
                         .context("log")
                 }
             }
@@ -468,15 +496,7 @@ pub trait Logger {
         self.print_result(res).unwrap();
         match res {
             Ok(()) => std::process::exit(0),
-            Err(err) => {
-                if let Some(NothingToDo(_)) = err.downcast_ref::<NothingToDo>() {
-                    std::process::exit(0);
-                } else if err.downcast_ref::<NotEnoughSpace>().is_some() {
-                    std::process::exit(3);
-                } else {
-                    std::process::exit(1);
-                }
-            }
+            Err(err) => std::process::exit(exit_code(err)),
         }
     }
 }
@@ -491,6 +511,9 @@ pub trait View {
 
     fn torrent_add_result(&mut self, res: &TorrentAddResult) -> Result<()>;
 
+    /// The id of an already added torrent for scripts
+    fn print_duplicate_id(&mut self, id: i64) -> Result<()>;
+
     fn torrent_action_ok<IT>(&mut self, torrents: IT, action: Action) -> Result<()>
     where
         IT: IntoIterator,
@@ -528,17 +551,11 @@ impl<O: WriteColor> StdLog<O> {
         }
     }
 
+    /// Without an explicit choice a non-empty `NO_COLOR` disables colors
     #[must_use]
     pub fn from_choice(want: Option<bool>, verbosity: u8) -> StdLog<BufferedStandardStream> {
-        let choice = if let Some(b) = want {
-            if b {
-                ColorChoice::Always
-            } else {
-                ColorChoice::Never
-            }
-        } else {
-            ColorChoice::Auto
-        };
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let choice = color_choice(want, no_color);
         let level = match verbosity {
             0 => log::LevelFilter::Info,
             1 => log::LevelFilter::Debug,
@@ -552,6 +569,15 @@ impl<O: WriteColor> StdLog<O> {
     }
 }
 
+fn color_choice(want: Option<bool>, no_color: bool) -> ColorChoice {
+    match want {
+        Some(true) => ColorChoice::Always,
+        Some(false) => ColorChoice::Never,
+        None if no_color => ColorChoice::Never,
+        None => ColorChoice::Auto,
+    }
+}
+
 impl Default for StdLog<BufferedStandardStream> {
     fn default() -> Self {
         Self::from_choice(None, 0)
@@ -576,17 +602,33 @@ pub struct Console<O: WriteColor, I: ReadLine> {
     pub base_dir: PathBuf,
     pub input: I,
     pub v_ask_existing: bool,
+    /// Ratio at or above which the ratio column is shown green
+    pub ratio_good: Option<f32>,
+    /// Ratio below which the ratio column is shown red
+    pub ratio_bad: Option<f32>,
+    /// Select with fzf when possible
+    pub pick: bool,
+    /// Show the hash as the first column
+    pub show_hash: Option<HashColumn>,
+    pub prompts: Prompts,
 }
 
 pub enum Action {
     TorrentAction(TorrentAction),
-    SetLocation { moved: bool },
+    SetLocation {
+        moved: bool,
+    },
+    Priority(PriorityLevel),
+    Group(String),
+    /// Whether the session wide speed limits apply
+    SessionLimits(bool),
 }
 impl<O: WriteColor, I: ReadLine> View for Console<O, I> {
     type Logger = StdLog<O>;
 
-    fn ask_retry(&mut self, _err: &anyhow::Error) -> Result<bool> {
-        unimplemented!();
+    fn ask_retry(&mut self, err: &anyhow::Error) -> Result<bool> {
+        print_error!(self.log(), "{:#}", err)?;
+        self.yesno("Retry?")
     }
 
     fn ask_existing(&mut self, name: &[u8], modified: u64) -> Result<bool> {
@@ -605,6 +647,14 @@ impl<O: WriteColor, I: ReadLine> View for Console<O, I> {
         &mut self.log
     }
 
+    // Asked for explicitly so not quieted
+    fn print_duplicate_id(&mut self, id: i64) -> Result<()> {
+        let out = self.log.out();
+        writeln!(out, "{id}")?;
+        out.flush()?;
+        Ok(())
+    }
+
     // TODO print torrent names without quieying them because no changes are seen yet anyway
     fn torrent_action_ok<IT>(&mut self, torrents: IT, action: Action) -> Result<()>
     where
@@ -625,6 +675,21 @@ impl<O: WriteColor, I: ReadLine> View for Console<O, I> {
             Action::TorrentAction(TorrentAction::Stop) => print_info!(&mut self.log, "Stopped:")?,
             Action::SetLocation { moved: false } => print_info!(&mut self.log, "Location set")?,
             Action::SetLocation { moved: true } => print_info!(&mut self.log, "Torrent moved")?,
+            Action::Priority(level) => {
+                let level = match level {
+                    PriorityLevel::High => "high",
+                    PriorityLevel::Normal => "normal",
+                    PriorityLevel::Low => "low",
+                };
+                print_info!(&mut self.log, "Priority set to {}:", level)?;
+            }
+            Action::Group(name) => print_info!(&mut self.log, "Group set to {}:", name)?,
+            Action::SessionLimits(true) => {
+                print_info!(&mut self.log, "Session limits honored by:")?;
+            }
+            Action::SessionLimits(false) => {
+                print_info!(&mut self.log, "Session limits ignored by:")?;
+            }
         }
         for t in torrents {
             let tor = t.borrow();
@@ -749,15 +814,7 @@ impl<O: WriteColor> Logger for StdLog<O> {
         }
         match res {
             Ok(()) => std::process::exit(0),
-            Err(err) => {
-                if let Some(NothingToDo(_)) = err.downcast_ref::<NothingToDo>() {
-                    std::process::exit(0);
-                } else if let Some(_) = err.downcast_ref::<NotEnoughSpace>() {
-                    std::process::exit(3);
-                } else {
-                    std::process::exit(1);
-                }
-            }
+            Err(err) => std::process::exit(exit_code(err)),
         }
     }
 
@@ -773,6 +830,11 @@ impl<O: WriteColor> Console<O, Stdin> {
             base_dir,
             input: stdin(),
             v_ask_existing,
+            ratio_good: None,
+            ratio_bad: None,
+            pick: false,
+            show_hash: None,
+            prompts: Prompts::default(),
         }
     }
 }
@@ -813,22 +875,98 @@ impl<O: WriteColor, I: ReadLine> Console<O, I> {
         Ok(reply)
     }
 
-    fn yesno(&mut self, question: &str) -> Result<bool> {
+    pub fn yesno(&mut self, question: &str) -> Result<bool> {
         loop {
-            write!(self.log.out(), "{question} [y/N]: ")?;
+            write!(
+                self.log.out(),
+                "{question} [{}/{}]: ",
+                self.prompts.yes,
+                self.prompts.no.to_uppercase()
+            )?;
             self.log.out().flush()?;
 
             let ans = self.read_reply()?;
 
-            if ans == "y" {
+            if self.prompts.is_yes(&ans) {
                 return Ok(true);
-            } else if ans.is_empty() || ans == "n" || ans == "N" {
+            } else if self.prompts.is_no(&ans) {
                 return Ok(false);
             }
             print_warn!(self.log(), "Invalid selection '{ans}'")?;
         }
     }
 
+    // Select with fzf. Returns None if it is not available so that we can fall back to asking
+    // the ids.
+    fn pick<TOR>(&mut self, torrents: &[TOR], need_one: bool) -> Result<Option<Vec<usize>>>
+    where
+        TOR: Borrow<Torrent>,
+    {
+        if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+            return Ok(None);
+        }
+        let mut cmd = Command::new("fzf");
+        // The lines are prefixed with the index so that they can be mapped back reliably
+        cmd.args(["--read0", "--print0", "--delimiter=\t", "--with-nth=2.."])
+            .arg(format!("--header={}", DisplayTorrent::get_header(false)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped());
+        if !need_one {
+            cmd.arg("--multi");
+        }
+        let mut child = match cmd.spawn() {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                print_debug!(self.log(), "fzf not found")?;
+                return Ok(None);
+            }
+            res => res?,
+        };
+        {
+            let mut stdin = child.stdin.take().context("fzf stdin")?;
+            for (i, t) in torrents.iter().enumerate() {
+                write!(
+                    stdin,
+                    "{}\t{}\0",
+                    i,
+                    DisplayTorrent {
+                        torrent: t.borrow(),
+                        base_dir: &self.base_dir
+                    }
+                )?;
+            }
+        }
+        let output = child.wait_with_output()?;
+        // 1 is no match and 130 is interrupted
+        if !output.status.success() {
+            return Ok(Some(vec![]));
+        }
+        output
+            .stdout
+            .split(|&c| c == b'\0')
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let line = String::from_utf8_lossy(line);
+                let idx = line.split('\t').next().unwrap_or_default();
+                match idx.parse::<usize>() {
+                    Ok(i) if i < torrents.len() => Ok(i),
+                    _ => Err(anyhow!("invalid fzf selection: {}", line)),
+                }
+            })
+            .collect::<Result<Vec<usize>>>()
+            .map(Some)
+    }
+
+    /// True if the user retypes the count
+    pub fn confirm_count(&mut self, count: usize, action: &str) -> Result<bool> {
+        write!(
+            self.log.out(),
+            "Type {count} to {action} {count} torrents: "
+        )?;
+        self.log.out().flush()?;
+        let ans = self.read_reply()?;
+        Ok(ans.trim() == count.to_string())
+    }
+
     // Return the array ids of selected torrents
     pub fn confirm<TOR>(
         &mut self,
@@ -841,8 +979,22 @@ impl<O: WriteColor, I: ReadLine> Console<O, I> {
         if torrents.is_empty() {
             bail!(NoMatches);
         }
-        self.print_filtered(torrents.iter().map(Borrow::borrow))
-            .context("print_filtered")?;
+
+        if self.pick && !matches!(action, Some(ConfirmAction::All)) {
+            let need_one = matches!(action, Some(ConfirmAction::One));
+            if let Some(selected) = self.pick(torrents, need_one).context("pick")? {
+                return Ok(selected);
+            }
+        }
+
+        // shown also when quiet if the user picks from it
+        if matches!(action, Some(ConfirmAction::All)) {
+            self.print_filtered(torrents.iter().map(Borrow::borrow), true, false)
+        } else {
+            let (writer, out) = self.writer_and_out();
+            writer.filtered(out, torrents.iter().map(Borrow::borrow), true, false, false)
+        }
+        .context("print_filtered")?;
 
         let mut need_one = false;
         match action {
@@ -852,25 +1004,30 @@ impl<O: WriteColor, I: ReadLine> Console<O, I> {
         }
 
         if torrents.len() == 1 {
-            if self.yesno("Select").context("yesno")? {
+            let select = self.prompts.select.clone();
+            if self.yesno(&select).context("yesno")? {
                 Ok(vec![0])
             } else {
                 bail!(NothingToDo("No selection"));
             }
         } else {
             loop {
+                let Prompts {
+                    select, all, no, ..
+                } = &self.prompts;
+                let no = no.to_uppercase();
                 if need_one {
-                    write!(self.log.out(), "Select [{{n}}/N]: ")?;
+                    write!(self.log.out(), "{select} [{{n}}/{no}]: ")?;
                 } else {
-                    write!(self.log.out(), "Select [a/{{n}}/N]: ")?;
+                    write!(self.log.out(), "{select} [{all}/{{n}}/{no}]: ")?;
                 }
                 self.log.out().flush()?;
 
                 let ans = self.read_reply()?;
 
-                if ans.is_empty() || ans == "n" || ans == "N" {
+                if self.prompts.is_no(&ans) {
                     return Ok(vec![]);
-                } else if !need_one && ans == "a" {
+                } else if !need_one && self.prompts.is_all(&ans) {
                     return Ok((0..torrents.len()).collect());
                 }
                 let n = ans.parse::<i64>();
@@ -894,57 +1051,368 @@ impl<O: WriteColor, I: ReadLine> Console<O, I> {
         }
     }
 
-    pub fn print_filtered<IT>(&mut self, torrents: IT) -> Result<()>
+    pub fn print_files(&mut self, torrent: &Torrent) -> Result<()> {
+        let out = self.log.out();
+        writeln!(out, "{}", Maybe(torrent.name.as_ref(), false))?;
+        for file in torrent.files.as_ref().context("undefined files")? {
+            writeln!(out, "{:8}  {}", ByteSize(file.length), file.name)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Borrows the writer and the output separately so that both can be used at once
+    pub fn writer_and_out(&mut self) -> (QueryWriter<'_>, &mut O) {
+        (
+            QueryWriter {
+                base_dir: &self.base_dir,
+                ratio_good: self.ratio_good,
+                ratio_bad: self.ratio_bad,
+                show_hash: self.show_hash,
+            },
+            self.log.out(),
+        )
+    }
+
+    /// Skipped when quiet
+    pub fn print_filtered<IT>(&mut self, torrents: IT, header: bool, swarm: bool) -> Result<()>
     where
         IT: IntoIterator,
         IT::Item: Borrow<Torrent>,
     {
-        writeln!(self.log.out(), "{}", DisplayTorrent::get_header())?;
+        if self.log.level < log::LevelFilter::Info {
+            return Ok(());
+        }
+        let (writer, out) = self.writer_and_out();
+        writer.filtered(out, torrents, header, swarm, false)
+    }
+
+    /// The shared json output of the reports
+    pub fn print_json<T: Serialize>(&mut self, report: &T) -> Result<()> {
+        let out = self.log.out();
+        serde_json::to_writer(&mut *out, report)?;
+        writeln!(out)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    pub fn print_space(
+        &mut self,
+        dirs: &[(PathBuf, DirSpace)],
+        quota: u64,
+        format: OutputFormat,
+    ) -> Result<()> {
+        if format == OutputFormat::Json {
+            #[derive(Serialize)]
+            struct Dir<'a> {
+                dir: &'a Path,
+                #[serde(flatten)]
+                space: &'a DirSpace,
+            }
+            #[derive(Serialize)]
+            struct Report<'a> {
+                quota: u64,
+                dirs: Vec<Dir<'a>>,
+            }
+            return self.print_json(&Report {
+                quota,
+                dirs: dirs.iter().map(|(dir, space)| Dir { dir, space }).collect(),
+            });
+        }
+        let out = self.log.out();
+        writeln!(out, "   Free     Safe    Total    Quota  Dir")?;
+        for (dir, space) in dirs {
+            writeln!(
+                out,
+                "{:7}  {:7}  {:7}  {:7}  {}",
+                ByteSize(space.free),
+                ByteSize(space.safe),
+                ByteSize(space.total_size),
+                ByteSize(quota),
+                dir.display()
+            )?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
 
-        let mut total_size = 0;
-        let mut total_up = 0;
-        let mut total_down = 0;
+/// Writes the query results to any output
+pub struct QueryWriter<'a> {
+    pub base_dir: &'a Path,
+    /// Ratio at or above which the ratio column is shown green
+    pub ratio_good: Option<f32>,
+    /// Ratio below which the ratio column is shown red
+    pub ratio_bad: Option<f32>,
+    pub show_hash: Option<HashColumn>,
+}
+
+impl QueryWriter<'_> {
+    fn ratio_color(&self, ratio: Option<f32>) -> Option<Color> {
+        // negative ratios are the "not available" and "infinite" sentinels
+        let ratio = ratio.filter(|&r| r >= 0.0)?;
+        if self.ratio_good.is_some_and(|good| ratio >= good) {
+            Some(Color::Green)
+        } else if self.ratio_bad.is_some_and(|bad| ratio < bad) {
+            Some(Color::Red)
+        } else {
+            None
+        }
+    }
+
+    /// Every name is terminated, with NUL for `print0`, like `find -print0`
+    pub fn names<W, IT>(
+        &self,
+        out: &mut W,
+        torrents: IT,
+        zsh_escape: bool,
+        print0: bool,
+    ) -> Result<()>
+    where
+        W: Write,
+        IT: IntoIterator,
+        IT::Item: Borrow<Torrent>,
+    {
+        let end = if print0 { '\0' } else { '\n' };
         for t in torrents {
             let tor = t.borrow();
+            let name = tor.name.as_ref().or(tor.hash_string.as_ref());
+            if zsh_escape {
+                write!(out, "{}{end}", Maybe(name.map(|n| escape::zsh(n)), false))?;
+            } else {
+                write!(out, "{}{end}", Maybe(name, false))?;
+            }
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    pub fn templated<W, IT>(&self, out: &mut W, torrents: IT, template: &Template) -> Result<()>
+    where
+        W: Write,
+        IT: IntoIterator,
+        IT::Item: Borrow<Torrent>,
+    {
+        for t in torrents {
             writeln!(
-                self.log.out(),
+                out,
                 "{}",
-                DisplayTorrent {
-                    torrent: tor,
-                    base_dir: &self.base_dir
-                }
+                template.render_torrent(t.borrow(), self.base_dir)
             )?;
-            let downloaded_size = tor.size_when_done.and_then(|x| {
-                if x < 0 {
-                    None
-                } else {
-                    tor.left_until_done
-                        .and_then(|z| if z < 0 { None } else { Some(z) })
-                        .map(|y| x - y)
-                }
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// The format is documented in `QueryCmd::porcelain` and must stay compatible
+    pub fn porcelain<W, IT>(&self, out: &mut W, torrents: IT) -> Result<()>
+    where
+        W: Write,
+        IT: IntoIterator,
+        IT::Item: Borrow<Torrent>,
+    {
+        fn field<T: fmt::Display>(value: Option<T>) -> String {
+            value.map_or_else(|| "-".to_string(), |v| v.to_string())
+        }
+        for t in torrents {
+            let tor = t.borrow();
+            let status = tor.status.map(|s| {
+                MyTorrentStatus::from(s)
+                    .to_possible_value()
+                    .map_or_else(String::new, |v| v.get_name().to_string())
             });
-            total_size += downloaded_size.unwrap_or(0);
-            total_up += tor.rate_upload.map_or(0, |x| if x < 0 { 0 } else { x });
-            total_down += tor.rate_download.map_or(0, |x| if x < 0 { 0 } else { x });
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                field(tor.id),
+                field(tor.hash_string.as_ref()),
+                field(status),
+                field(tor.percent_done),
+                field(tor.size_when_done),
+                field(tor.left_until_done),
+                field(tor.rate_upload),
+                field(tor.rate_download),
+                field(tor.upload_ratio),
+                field(tor.added_date),
+                field(tor.download_dir.as_deref().map(escape::porcelain)),
+                field(tor.name.as_deref().map(escape::porcelain)),
+                field(tor.seconds_seeding),
+            )?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// `swarm` adds the Seeds and Peers columns and `seed_time` the Seed column
+    pub fn filtered<W, IT>(
+        &self,
+        out: &mut W,
+        torrents: IT,
+        header: bool,
+        swarm: bool,
+        seed_time: bool,
+    ) -> Result<()>
+    where
+        W: WriteColor,
+        IT: IntoIterator,
+        IT::Item: Borrow<Torrent>,
+    {
+        let hash_width = self.show_hash.map(HashColumn::width);
+        if header {
+            if let Some(width) = hash_width {
+                write!(out, "{:width$}  ", "Hash")?;
+            }
+            if swarm {
+                write!(out, "Seeds  Peers  ")?;
+            }
+            writeln!(out, "{}", DisplayTorrent::get_header(seed_time))?;
         }
 
+        let mut totals = Totals::default();
+        for t in torrents {
+            let tor = t.borrow();
+            let dt = DisplayTorrent {
+                torrent: tor,
+                base_dir: self.base_dir,
+            };
+            if let Some(width) = hash_width {
+                write!(out, "{:width$}  ", dt.hash(width))?;
+            }
+            if swarm {
+                let (seeds, peers) = dt.swarm();
+                write!(out, "{seeds:5}  {peers:5}  ")?;
+            }
+            write!(out, "{}", dt.head())?;
+            if let Some(color) = self.ratio_color(tor.upload_ratio) {
+                out.set_color(ColorSpec::new().set_fg(Some(color)))?;
+                write!(out, "{:5.1}", dt.ratio())?;
+                out.reset()?;
+            } else {
+                write!(out, "{:5.1}", dt.ratio())?;
+            }
+            writeln!(out, "{}", dt.tail(seed_time))?;
+            totals.add(tor);
+        }
+
+        if let Some(width) = hash_width {
+            write!(out, "{:width$}  ", "")?;
+        }
+        if swarm {
+            write!(out, "{:14}", "")?;
+        }
         writeln!(
-            self.log.out(),
+            out,
             "Sum:  {:14}  {:26}  {:7}",
-            ByteSize(total_size),
-            ByteSize(total_up),
-            ByteSize(total_down)
+            ByteSize(totals.size),
+            ByteSize(totals.up),
+            ByteSize(totals.down)
         )?;
 
-        self.log.out().flush()?;
+        out.flush()?;
+        Ok(())
+    }
+
+    pub fn grouped<W, IT>(&self, out: &mut W, torrents: IT) -> Result<()>
+    where
+        W: Write,
+        IT: IntoIterator,
+        IT::Item: Borrow<Torrent>,
+    {
+        let mut groups: BTreeMap<String, Totals> = BTreeMap::new();
+        let mut total = Totals::default();
+        for t in torrents {
+            let tor = t.borrow();
+            let dt = DisplayTorrent {
+                torrent: tor,
+                base_dir: self.base_dir,
+            };
+            groups
+                .entry(dt.download_dir().to_string())
+                .or_default()
+                .add(tor);
+            total.add(tor);
+        }
+
+        writeln!(out, "Count    Have      Up    Down  Dir")?;
+        for (dir, totals) in &groups {
+            writeln!(out, "{totals}  {dir}")?;
+        }
+        writeln!(out, "{total}  Sum")?;
+        out.flush()?;
         Ok(())
     }
 }
 
+/// Running totals of the summable columns
+#[derive(Default)]
+struct Totals {
+    count: usize,
+    size: i64,
+    up: i64,
+    down: i64,
+}
+
+impl Totals {
+    fn add(&mut self, tor: &Torrent) {
+        let downloaded_size = tor.size_when_done.and_then(|x| {
+            if x < 0 {
+                None
+            } else {
+                tor.left_until_done
+                    .and_then(|z| if z < 0 { None } else { Some(z) })
+                    .map(|y| x - y)
+            }
+        });
+        self.count += 1;
+        self.size += downloaded_size.unwrap_or(0);
+        self.up += tor.rate_upload.map_or(0, |x| if x < 0 { 0 } else { x });
+        self.down += tor.rate_download.map_or(0, |x| if x < 0 { 0 } else { x });
+    }
+}
+
+impl fmt::Display for Totals {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{:5}  {:6}  {:6}  {:6}",
+            self.count,
+            ByteSize(self.size),
+            ByteSize(self.up),
+            ByteSize(self.down)
+        )
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
     //use pretty_assertions::assert_eq;
+
+    #[test]
+    fn ratio_color() {
+        let mut writer = QueryWriter {
+            base_dir: Path::new(""),
+            ratio_good: None,
+            ratio_bad: None,
+            show_hash: None,
+        };
+        assert_eq!(writer.ratio_color(Some(5.0)), None);
+        writer.ratio_good = Some(2.0);
+        writer.ratio_bad = Some(0.5);
+        assert_eq!(writer.ratio_color(Some(2.0)), Some(Color::Green));
+        assert_eq!(writer.ratio_color(Some(1.0)), None);
+        assert_eq!(writer.ratio_color(Some(0.1)), Some(Color::Red));
+        assert_eq!(writer.ratio_color(Some(-1.0)), None);
+        assert_eq!(writer.ratio_color(None), None);
+    }
+    #[test]
+    fn no_color() {
+        assert_eq!(color_choice(None, false), ColorChoice::Auto);
+        assert_eq!(color_choice(None, true), ColorChoice::Never);
+        assert_eq!(color_choice(Some(true), true), ColorChoice::Always);
+        assert_eq!(color_choice(Some(false), false), ColorChoice::Never);
+    }
+
     #[test]
     #[ignore]
     fn testmail() -> crate::errors::Result<()> {
@@ -1,20 +1,71 @@
-use std::{path::PathBuf, time::SystemTime};
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
 
-use rusqlite::Connection;
+use rusqlite::{Connection, ErrorCode, OpenFlags};
 
 use crate::errors::*;
 
 use log::debug;
 
+// trmv is run from hooks that can overlap so wait for the other process to finish its write
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+const STORE_RETRIES: u32 = 3;
+
+#[derive(Debug)]
 pub struct DBSqlite {
     conn: Option<Connection>,
+    ro_conn: Option<Connection>,
     path: Option<PathBuf>,
 }
 
 impl DBSqlite {
     #[must_use]
     pub fn new(path: Option<PathBuf>) -> Self {
-        Self { conn: None, path }
+        Self {
+            conn: None,
+            ro_conn: None,
+            path,
+        }
+    }
+
+    // Lookups should work on a read only mount and must not create the db. Returns None if there
+    // is nothing to look up from yet.
+    fn init_read_only(&mut self) -> Result<Option<&Connection>> {
+        if let Some(ref c) = self.conn {
+            return Ok(Some(c));
+        }
+        if self.ro_conn.is_none() {
+            let Some(path) = self.path.as_ref() else {
+                bail!("The sqlite db is not enabled");
+            };
+            if !path.exists() {
+                debug!("no db yet");
+                return Ok(None);
+            }
+            debug!("initializing read only db");
+            let conn = Connection::open_with_flags(
+                path,
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            )?;
+            conn.busy_timeout(BUSY_TIMEOUT)?;
+            if !Self::has_tables(&conn)? {
+                debug!("no tables yet");
+                return Ok(None);
+            }
+            self.ro_conn = Some(conn);
+        }
+        Ok(self.ro_conn.as_ref())
+    }
+
+    fn has_tables(conn: &Connection) -> Result<bool> {
+        let mut statement = conn.prepare(
+            "
+            SELECT name FROM sqlite_master WHERE type='table' AND name='torrents';
+            ",
+        )?;
+        Ok(statement.exists([])?)
     }
 
     fn init(&mut self) -> Result<&mut Connection> {
@@ -25,16 +76,18 @@ impl DBSqlite {
         }
 
         let conn = Connection::open(self.path.as_ref().unwrap())?;
-        let mut statement = conn.prepare(
-            "
-            SELECT name FROM sqlite_master WHERE type='table' AND name='torrents';
-            ",
-        )?;
-        if statement.query([])?.next()?.is_none() {
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        if !Self::has_tables(&conn)? {
             Self::create_tables(&conn)?;
         }
-        drop(statement);
+        // came after the torrents table so older dbs lack it
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS seen (hash TEXT PRIMARY KEY, timestamp BIGINT);",
+            [],
+        )?;
 
+        // lookups use the read-write connection from now on
+        self.ro_conn = None;
         self.conn = Some(conn);
         Ok(self.conn.as_mut().unwrap())
     }
@@ -52,6 +105,10 @@ impl DBSqlite {
 pub trait DB {
     fn store(&mut self, hsh: &str) -> Result<()>;
     fn has(&mut self, hsh: &str) -> Result<Option<u64>>;
+    /// Records the hashes as shown by a query
+    fn mark_seen(&mut self, hashes: &[&str]) -> Result<()>;
+    /// The hashes that no query has shown yet
+    fn unseen_hashes<'a>(&mut self, hashes: &[&'a str]) -> Result<Vec<&'a str>>;
 }
 
 impl DB for DBSqlite {
@@ -64,11 +121,24 @@ impl DB for DBSqlite {
         let timestamp = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)?
             .as_secs();
-        conn.execute(
-            "INSERT OR IGNORE INTO torrents (hash, timestamp) VALUES (?1, ?2);",
-            (hsh, timestamp),
-        )?;
-        Ok(())
+        let mut tries = 0;
+        loop {
+            match conn.execute(
+                "INSERT OR IGNORE INTO torrents (hash, timestamp) VALUES (?1, ?2);",
+                (hsh, timestamp),
+            ) {
+                Err(rusqlite::Error::SqliteFailure(err, _))
+                    if err.code == ErrorCode::DatabaseBusy && tries < STORE_RETRIES =>
+                {
+                    tries += 1;
+                    debug!("db busy, retry {tries}");
+                }
+                res => {
+                    res?;
+                    return Ok(());
+                }
+            }
+        }
     }
 
     fn has(&mut self, hsh: &str) -> Result<Option<u64>> {
@@ -76,7 +146,9 @@ impl DB for DBSqlite {
             debug!("not enabled");
             return Ok(None);
         }
-        let conn = self.init()?;
+        let Some(conn) = self.init_read_only()? else {
+            return Ok(None);
+        };
 
         let mut statement = conn.prepare(
             "
@@ -88,4 +160,55 @@ impl DB for DBSqlite {
         }
         Ok(None)
     }
+
+    fn mark_seen(&mut self, hashes: &[&str]) -> Result<()> {
+        if self.path.is_none() {
+            bail!("The sqlite db is not enabled");
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs();
+        let conn = self.init()?;
+        let tx = conn.transaction()?;
+        {
+            let mut statement =
+                tx.prepare("INSERT OR IGNORE INTO seen (hash, timestamp) VALUES (?1, ?2);")?;
+            for hsh in hashes {
+                statement.execute((hsh, timestamp))?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn unseen_hashes<'a>(&mut self, hashes: &[&'a str]) -> Result<Vec<&'a str>> {
+        if self.path.is_none() {
+            bail!("The sqlite db is not enabled");
+        }
+        let conn = self.init()?;
+        let mut statement = conn.prepare("SELECT 1 FROM seen WHERE hash = ?1;")?;
+        let mut unseen = Vec::new();
+        for &hsh in hashes {
+            if !statement.exists([hsh])? {
+                unseen.push(hsh);
+            }
+        }
+        Ok(unseen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seen() {
+        let path = std::env::temp_dir().join(format!("trctl-seen-{}.sqlite3", std::process::id()));
+        let mut db = DBSqlite::new(Some(path.clone()));
+        assert_eq!(db.unseen_hashes(&["a", "b"]).unwrap(), ["a", "b"]);
+        db.mark_seen(&["a"]).unwrap();
+        assert_eq!(db.unseen_hashes(&["a", "b"]).unwrap(), ["b"]);
+        assert!(DBSqlite::new(None).mark_seen(&["a"]).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
 }
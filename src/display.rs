@@ -1,6 +1,7 @@
 use std::fmt;
 use std::path::Path;
-use transmission_rpc::types::{Torrent as TrTorrent, TorrentStatus};
+use std::time::SystemTime;
+use transmission_rpc::types::{Torrent as TrTorrent, TorrentAction, TorrentStatus, TrackerStat};
 
 pub struct Torrent<'a> {
     pub torrent: &'a TrTorrent,
@@ -9,9 +10,13 @@ pub struct Torrent<'a> {
 
 impl<'a> Torrent<'a> {
     #[must_use]
-    pub const fn get_header() -> &'static str {
+    pub const fn get_header(seed_time: bool) -> &'static str {
         //"{:4}   {:>4}  {:>7}  {:>7}  {:>8}  {:>7}  {:>7}  {:5}  {:9}  Name",
-        "ID     Done     Have     Size       ETA       Up     Down  Ratio  Status     Name"
+        if seed_time {
+            "ID     Done     Have     Size       ETA       Up     Down  Ratio    Age   Seed  Status     Name"
+        } else {
+            "ID     Done     Have     Size       ETA       Up     Down  Ratio    Age  Status     Name"
+        }
     }
 
     #[must_use]
@@ -52,6 +57,120 @@ impl<'a> Torrent<'a> {
     pub fn id(&self) -> impl fmt::Display {
         Maybe(self.torrent.id, true)
     }
+
+    /// The hash cut to `len` characters
+    #[must_use]
+    pub fn hash(&self, len: usize) -> impl fmt::Display + 'a {
+        Maybe(
+            self.torrent
+                .hash_string
+                .as_deref()
+                .map(|h| h.get(..len).unwrap_or(h)),
+            false,
+        )
+    }
+
+    /// Total time spent seeding
+    #[must_use]
+    pub fn seed_time(&self) -> impl fmt::Display {
+        Maybe(self.torrent.seconds_seeding.map(CompactDuration), true)
+    }
+
+    /// The most seeders and leechers reported by a tracker. The counts of the trackers overlap
+    /// so they are not summed. -1 is a tracker that does not know.
+    #[must_use]
+    pub fn swarm(&self) -> (Maybe<i64>, Maybe<i64>) {
+        let stats = self.torrent.tracker_stats.as_deref().unwrap_or_default();
+        let most = |count: fn(&TrackerStat) -> i64| {
+            Maybe(stats.iter().map(count).filter(|&c| c >= 0).max(), true)
+        };
+        (most(|s| s.seeder_count), most(|s| s.leecher_count))
+    }
+
+    /// The columns before the ratio
+    #[must_use]
+    pub fn head(&'a self) -> impl fmt::Display + 'a {
+        Head(self)
+    }
+
+    #[must_use]
+    pub fn ratio(&self) -> impl fmt::Display {
+        Maybe(self.torrent.upload_ratio, true)
+    }
+
+    /// The columns after the ratio. `seed_time` adds the Seed column.
+    #[must_use]
+    pub fn tail(&'a self, seed_time: bool) -> impl fmt::Display + 'a {
+        Tail(self, seed_time)
+    }
+
+    /// Time since the torrent was added
+    #[must_use]
+    pub fn age(&self) -> impl fmt::Display {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX));
+        Maybe(
+            self.torrent
+                .added_date
+                .map(|added| added.timestamp())
+                .filter(|&added| added > 0)
+                .map(|added| CompactDuration(now - added)),
+            true,
+        )
+    }
+}
+
+struct Head<'a>(&'a Torrent<'a>);
+
+impl fmt::Display for Head<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let torrent = self.0.torrent;
+        write!(
+            formatter,
+            "{:4}{}  {:3.0}%  {:7.1}  {:7.1}  {:>8}  {:7.1}  {:7.1}  ",
+            self.0.id(),
+            self.0.error_mark(),
+            self.0.percent_done(),
+            self.0.downloaded_size(),
+            Maybe(torrent.size_when_done.map(ByteSize), true),
+            Maybe(
+                torrent.eta.map(|e| Eta {
+                    eta: e,
+                    left_until_done: torrent.left_until_done
+                }),
+                true
+            ),
+            Maybe(torrent.rate_upload.map(ByteSize), true),
+            Maybe(torrent.rate_download.map(ByteSize), true),
+        )
+    }
+}
+
+struct Tail<'a>(&'a Torrent<'a>, bool);
+
+impl fmt::Display for Tail<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let torrent = self.0.torrent;
+        write!(formatter, "  {:>5}", self.0.age())?;
+        if self.1 {
+            write!(formatter, "  {:>5}", self.0.seed_time())?;
+        }
+        write!(
+            formatter,
+            "  {:9}  {}/{}",
+            Maybe(Status::from_torrent(torrent), false),
+            self.0.download_dir(),
+            Maybe(torrent.name.as_ref(), false),
+        )?;
+
+        match torrent.error_string {
+            Some(ref s) if !s.is_empty() => write!(formatter, "\n       error: {s}")?,
+            _ => (),
+        }
+
+        Ok(())
+    }
 }
 
 struct DlDir<'a>(&'a Torrent<'a>);
@@ -103,45 +222,22 @@ impl fmt::Display for Torrent<'_> {
     ///         "{}",
     ///         Torrent { torrent: &tor, base_dir: &config.base_dir },
     ///     ),
-    ///     "  NA*   NA%       NA       NA        NA       NA       NA     NA  NA         NA/NA"
+    ///     "  NA*   NA%       NA       NA        NA       NA       NA     NA     NA  NA         NA/NA"
     /// );
     /// let tor2 = trctl::client::test_torrent(70, "testing.pdf");
     /// assert_eq!(
     ///     format!("{}", Torrent { torrent: &tor2, base_dir: &config.base_dir }),
-    ///     "  70   100%     2.4G     2.4G   Unknown        0        0    0.8  Idle       dl//testing.pdf"
+    ///     "  70   100%     2.4G     2.4G   Unknown        0        0    0.8     NA  Stalled    dl//testing.pdf"
     ///     );
     ///  ```
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        let Torrent { torrent, .. } = self;
         write!(
             formatter,
-            "{:4}{}  {:3.0}%  {:7.1}  {:7.1}  {:>8}  {:7.1}  {:7.1}  {:5.1}  {:9}  {}/{}",
-            self.id(),
-            self.error_mark(),
-            self.percent_done(),
-            self.downloaded_size(),
-            Maybe(torrent.size_when_done.map(ByteSize), true),
-            Maybe(
-                torrent.eta.map(|e| Eta {
-                    eta: e,
-                    left_until_done: torrent.left_until_done
-                }),
-                true
-            ),
-            Maybe(torrent.rate_upload.map(ByteSize), true),
-            Maybe(torrent.rate_download.map(ByteSize), true),
-            Maybe(torrent.upload_ratio, true),
-            Maybe(Status::from_torrent(torrent), false),
-            self.download_dir(),
-            Maybe(torrent.name.as_ref(), false),
-        )?;
-
-        match torrent.error_string {
-            Some(ref s) if !s.is_empty() => write!(formatter, "\n       error: {s}")?,
-            _ => (),
-        }
-
-        Ok(())
+            "{}{:5.1}{}",
+            self.head(),
+            self.ratio(),
+            self.tail(false)
+        )
     }
 }
 
@@ -200,6 +296,26 @@ impl<T: fmt::Display> fmt::Display for Maybe<T> {
     }
 }
 
+/// Name of an action for messages
+/// ```
+/// use trctl::display::ActionName;
+/// use transmission_rpc::types::TorrentAction;
+/// assert_eq!(ActionName(TorrentAction::StartNow).to_string(), "Start now");
+/// ```
+pub struct ActionName(pub TorrentAction);
+
+impl fmt::Display for ActionName {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(match self.0 {
+            TorrentAction::Start => "Start",
+            TorrentAction::StartNow => "Start now",
+            TorrentAction::Stop => "Stop",
+            TorrentAction::Verify => "Verify",
+            TorrentAction::Reannounce => "Reannounce",
+        })
+    }
+}
+
 pub struct Status {
     status: TorrentStatus,
     is_finished: Option<bool>,
@@ -207,6 +323,7 @@ pub struct Status {
     peers_getting_from_us: Option<i64>,
     peers_sending_to_us: Option<i64>,
     left_until_done: Option<i64>,
+    is_stalled: Option<bool>,
 }
 
 impl Status {
@@ -219,6 +336,7 @@ impl Status {
             peers_getting_from_us: torrent.peers_getting_from_us,
             peers_sending_to_us: torrent.peers_sending_to_us,
             left_until_done: torrent.left_until_done,
+            is_stalled: torrent.is_stalled,
         })
     }
 }
@@ -236,6 +354,7 @@ impl fmt::Display for Status {
             peers_getting_from_us,
             peers_sending_to_us,
             left_until_done,
+            is_stalled,
         } = self;
         let width = formatter.width().unwrap_or(0);
         match status {
@@ -267,6 +386,8 @@ impl fmt::Display for Status {
                 formatter,
                 "{:width$}",
                 match (peers_getting_from_us, peers_sending_to_us) {
+                    // in place of the activity to fit the column
+                    _ if is_stalled == Some(true) => "Stalled",
                     (None, _) | (_, None) => "ERROR",
                     (Some(x), Some(y)) if x != 0 && y != 0 => "Up & Down",
                     (_, Some(y)) if y != 0 => "Downloading",
@@ -286,6 +407,76 @@ impl fmt::Display for Status {
     }
 }
 
+// (seconds, short name, long name)
+// Months and years are coarse: a month is 30 days and a year 365 days.
+const DURATION_UNITS: [(i64, &str, &str); 7] = [
+    (1, "s", "sec"),
+    (60, "m", "min"),
+    (60 * 60, "h", "hrs"),
+    (60 * 60 * 24, "d", "days"),
+    (60 * 60 * 24 * 7, "w", "wks"),
+    (60 * 60 * 24 * 30, "mo", "mos"),
+    (60 * 60 * 24 * 365, "y", "yrs"),
+];
+
+/// The largest unit that fits in the duration and the duration in it
+fn duration_unit(secs: i64) -> (i64, &'static str, &'static str) {
+    let &(unit, short, long) = DURATION_UNITS
+        .iter()
+        .rev()
+        .find(|(unit, _, _)| secs >= *unit)
+        .unwrap_or(&DURATION_UNITS[0]);
+    (secs / unit, short, long)
+}
+
+/// Parse a duration given in the short units
+/// ```
+/// use trctl::display::parse_duration;
+/// assert_eq!(parse_duration("90s"), Some(90));
+/// assert_eq!(parse_duration("2w"), Some(2 * 7 * 24 * 60 * 60));
+/// assert_eq!(parse_duration("1mo"), Some(30 * 24 * 60 * 60));
+/// assert_eq!(parse_duration("2"), None);
+/// assert_eq!(parse_duration("w"), None);
+/// assert_eq!(parse_duration("2x"), None);
+/// ```
+#[must_use]
+pub fn parse_duration(s: &str) -> Option<i64> {
+    let (n, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit())?);
+    let n: i64 = n.parse().ok()?;
+    let &(secs, _, _) = DURATION_UNITS.iter().find(|(_, short, _)| *short == unit)?;
+    n.checked_mul(secs)
+}
+
+pub struct CompactDuration(pub i64);
+
+/// Short human readable duration
+/// ```
+/// use trctl::display::CompactDuration;
+/// assert_eq!(CompactDuration(20).to_string(), "20s");
+/// assert_eq!(CompactDuration(61).to_string(), "1m");
+/// assert_eq!(CompactDuration(2 * 60 * 60).to_string(), "2h");
+/// assert_eq!(CompactDuration(3 * 24 * 60 * 60 + 1).to_string(), "3d");
+/// assert_eq!(format!("{:5}", CompactDuration(3 * 24 * 60 * 60)), "   3d");
+/// assert_eq!(CompactDuration(13 * 24 * 60 * 60).to_string(), "1w");
+/// assert_eq!(format!("{:5}", CompactDuration(45 * 24 * 60 * 60)), "  1mo");
+/// assert_eq!(CompactDuration(800 * 24 * 60 * 60).to_string(), "2y");
+/// assert_eq!(CompactDuration(-5).to_string(), "0s");
+/// ```
+impl fmt::Display for CompactDuration {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        // clocks can be a bit off
+        let (n, short, _) = duration_unit(self.0.max(0));
+        let width = formatter.width().unwrap_or(0);
+        write!(
+            formatter,
+            "{:width$}{}",
+            n,
+            short,
+            width = width.saturating_sub(short.len())
+        )
+    }
+}
+
 pub struct Eta {
     pub eta: i64,
     pub left_until_done: Option<i64>,
@@ -298,6 +489,10 @@ pub struct Eta {
 /// assert_eq!(Eta { eta: 60, left_until_done: None }.to_string(), "1 min");
 /// assert_eq!(Eta { eta: 61, left_until_done: None }.to_string(), "1 min");
 /// assert_eq!(format!("{:7}", Eta { eta: 61, left_until_done: None }), "  1 min");
+/// assert_eq!(Eta { eta: 6 * 24 * 60 * 60, left_until_done: None }.to_string(), "6 days");
+/// assert_eq!(Eta { eta: 7 * 24 * 60 * 60, left_until_done: None }.to_string(), "1 wks");
+/// assert_eq!(format!("{:7}", Eta { eta: 60 * 24 * 60 * 60, left_until_done: None }), "  2 mos");
+/// assert_eq!(Eta { eta: 400 * 24 * 60 * 60, left_until_done: None }.to_string(), "1 yrs");
 /// ```
 impl fmt::Display for Eta {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
@@ -307,35 +502,14 @@ impl fmt::Display for Eta {
         } = self;
         let width = formatter.width().unwrap_or(0);
         if eta >= 0 {
-            if eta < 60 {
-                write!(
-                    formatter,
-                    "{:width$} sec",
-                    eta,
-                    width = if width < 4 { 0 } else { width - 4 }
-                )
-            } else if eta < (60 * 60) {
-                write!(
-                    formatter,
-                    "{:width$} min",
-                    eta / 60,
-                    width = if width < 4 { 0 } else { width - 4 }
-                )
-            } else if eta < (60 * 60 * 24) {
-                write!(
-                    formatter,
-                    "{:width$} hrs",
-                    eta / (60 * 60),
-                    width = if width < 4 { 0 } else { width - 4 }
-                )
-            } else {
-                write!(
-                    formatter,
-                    "{:width$} days",
-                    eta / (60 * 60 * 24),
-                    width = if width < 5 { 0 } else { width - 5 }
-                )
-            }
+            let (n, _, long) = duration_unit(eta);
+            write!(
+                formatter,
+                "{:width$} {}",
+                n,
+                long,
+                width = width.saturating_sub(long.len() + 1)
+            )
         } else {
             write!(
                 formatter,
@@ -392,11 +566,14 @@ impl fmt::Display for ByteSize<i64> {
     //((bytes + (1<<9) >> 10, 1<<8))
     //}
 
-    /// Human readable bytes in si units.
+    /// Human readable bytes in si units. The alternate form is the exact byte count.
     ///
     /// ```
     /// use trctl::display::ByteSize;
     /// assert_eq!(format!("{}", ByteSize(0i64)), "0");
+    /// assert_eq!(format!("{}", ByteSize(1536i64)), "1.5K");
+    /// assert_eq!(format!("{:#}", ByteSize(1536i64)), "1536");
+    /// assert_eq!(format!("{:#6}", ByteSize(-1536i64)), " -1536");
     /// assert_eq!(format!("{}", ByteSize(1023i64)), "1023");
     /// assert_eq!(format!("{}", ByteSize(-1023i64)), "-1023");
     /// assert_eq!(format!("{}", ByteSize(1024i64)), "1.0K");
@@ -409,7 +586,7 @@ impl fmt::Display for ByteSize<i64> {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         let bytes = self.0;
         let width = formatter.width().unwrap_or(0);
-        if bytes.abs() < 1024 {
+        if bytes.abs() < 1024 || formatter.alternate() {
             return write!(formatter, "{bytes:width$}");
         }
         #[allow(clippy::cast_precision_loss)]
@@ -418,11 +595,12 @@ impl fmt::Display for ByteSize<i64> {
 }
 
 impl fmt::Display for ByteSize<u64> {
-    /// Human readable bytes in si units.
+    /// Human readable bytes in si units. The alternate form is the exact byte count.
     ///
     /// ```
     /// use trctl::display::ByteSize;
     /// assert_eq!(format!("{}", ByteSize(0u64)), "0");
+    /// assert_eq!(format!("{:#}", ByteSize(1536u64)), "1536");
     /// assert_eq!(format!("{}", ByteSize(1023u64)), "1023");
     /// assert_eq!(format!("{}", ByteSize(1024u64)), "1.0K");
     /// assert_eq!(format!("{:5}", ByteSize(1025u64)), " 1.0K");
@@ -433,7 +611,7 @@ impl fmt::Display for ByteSize<u64> {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         let bytes = self.0;
         let width = formatter.width().unwrap_or(0);
-        if bytes < 1024 {
+        if bytes < 1024 || formatter.alternate() {
             return write!(formatter, "{bytes:width$}");
         }
         #[allow(clippy::cast_precision_loss)]
@@ -456,6 +634,7 @@ mod tests {
             peers_getting_from_us: Some(3),
             peers_sending_to_us: Some(2),
             left_until_done: Some(2323),
+            is_stalled: Some(true),
         };
         assert_eq!(format!("{status}"), "Queued");
         assert_eq!(format!("{status:7}"), "Queued ");
@@ -464,5 +643,9 @@ mod tests {
         assert_eq!(format!("{status:9}"), "Finished ");
         status.is_finished = Some(false);
         assert_eq!(status.to_string(), "Stopped");
+        status.status = TorrentStatus::Seeding;
+        assert_eq!(format!("{status:9}"), "Stalled  ");
+        status.is_stalled = Some(false);
+        assert_eq!(format!("{status:11}"), "Up & Down  ");
     }
 }
@@ -1,5 +1,27 @@
 pub use anyhow::{anyhow, bail, Context as _, Error, Result};
 
+/// Exit code for an error so that scripts can tell the failures apart:
+///
+/// * 0: [`NothingToDo`]
+/// * 1: any other error
+/// * 2: [`NoMatches`]
+/// * 3: [`NotEnoughSpace`]
+/// * 4: [`Multiple`]
+#[must_use]
+pub fn exit_code(err: &Error) -> i32 {
+    if err.downcast_ref::<NothingToDo>().is_some() {
+        0
+    } else if err.downcast_ref::<NoMatches>().is_some() {
+        2
+    } else if err.downcast_ref::<NotEnoughSpace>().is_some() {
+        3
+    } else if err.downcast_ref::<Multiple>().is_some() {
+        4
+    } else {
+        1
+    }
+}
+
 #[derive(Debug)]
 pub struct NoMatches;
 impl std::error::Error for NoMatches {}
@@ -36,6 +58,78 @@ impl std::fmt::Display for NotEnoughSpace {
     }
 }
 
+/// The rpc request did not get a response, which might go away on a retry
+#[derive(Debug)]
+pub struct RpcConnection(pub String);
+impl std::error::Error for RpcConnection {}
+impl std::fmt::Display for RpcConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rpc call: {}", self.0)
+    }
+}
+
+/// The part of a torrent file that was being parsed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TorrentParseStage {
+    Torrent,
+    Info,
+    Name,
+    Length,
+    Files,
+    InfoHash,
+    Announce,
+}
+
+impl std::fmt::Display for TorrentParseStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Torrent => "torrent dict",
+            Self::Info => "info dict",
+            Self::Name => "name",
+            Self::Length => "length",
+            Self::Files => "files",
+            Self::InfoHash => "info hash",
+            Self::Announce => "trackers",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum TorrentParseError {
+    Empty,
+    Bendy {
+        stage: TorrentParseStage,
+        error: bendy::decoding::Error,
+    },
+    Missing(TorrentParseStage),
+}
+impl std::error::Error for TorrentParseError {}
+impl std::fmt::Display for TorrentParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "Torrent file is empty"),
+            Self::Bendy { stage, error } => write!(f, "Invalid torrent {stage}: {error}"),
+            Self::Missing(stage) => write!(f, "Torrent {stage} could not be found"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct HashMismatch {
+    pub expected: String,
+    pub actual: String,
+}
+impl std::error::Error for HashMismatch {}
+impl std::fmt::Display for HashMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Info hash mismatch: expected {} but the torrent has {}",
+            self.expected, self.actual
+        )
+    }
+}
+
 #[derive(Debug)]
 pub struct MagnetURIError(pub magnet_uri::Error);
 impl std::fmt::Display for MagnetURIError {
@@ -49,3 +143,17 @@ impl std::fmt::Display for MagnetURIError {
     }
 }
 impl std::error::Error for MagnetURIError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(exit_code(&anyhow!(NothingToDo("x"))), 0);
+        assert_eq!(exit_code(&anyhow!("x")), 1);
+        assert_eq!(exit_code(&anyhow!(NoMatches)), 2);
+        assert_eq!(exit_code(&anyhow!(NotEnoughSpace).context("mv")), 3);
+        assert_eq!(exit_code(&anyhow!(Multiple(2))), 4);
+    }
+}
@@ -1,10 +1,21 @@
 // from: https://fullstackmilk.dev/efficiently_escaping_strings_using_cow_in_rust/
 use std::borrow::Cow;
 
+#[must_use]
 pub fn zsh(input: &'_ str) -> Cow<'_, str> {
+    escape_with(input, zsh_escape_char)
+}
+
+/// For the tab separated porcelain output
+#[must_use]
+pub fn porcelain(input: &'_ str) -> Cow<'_, str> {
+    escape_with(input, porcelain_escape_char)
+}
+
+fn escape_with(input: &'_ str, escape_char: fn(char) -> Option<&'static str>) -> Cow<'_, str> {
     // Iterate through the characters, checking if each one needs escaping
-    for (i, ch) in input.chars().enumerate() {
-        if zsh_escape_char(ch).is_some() {
+    for (i, ch) in input.char_indices() {
+        if escape_char(ch).is_some() {
             // At least one char needs escaping, so we need to return a brand
             // new `String` rather than the original
 
@@ -24,7 +35,7 @@ pub fn zsh(input: &'_ str) -> Cow<'_, str> {
             // Escape the remaining characters if they need it and add them to
             // our escaped string
             for ch in input[i..].chars() {
-                match zsh_escape_char(ch) {
+                match escape_char(ch) {
                     Some(escaped_char) => escaped_string.push_str(escaped_char),
                     None => escaped_string.push(ch),
                 }
@@ -39,9 +50,50 @@ pub fn zsh(input: &'_ str) -> Cow<'_, str> {
     Cow::Borrowed(input)
 }
 
+// The characters that are special in zsh completion matches and descriptions
 fn zsh_escape_char(ch: char) -> Option<&'static str> {
     match ch {
+        '\\' => Some("\\\\"),
         ':' => Some("\\:"),
+        ' ' => Some("\\ "),
+        '(' => Some("\\("),
+        ')' => Some("\\)"),
+        '[' => Some("\\["),
+        ']' => Some("\\]"),
+        '%' => Some("%%"),
         _ => None,
     }
 }
+
+fn porcelain_escape_char(ch: char) -> Option<&'static str> {
+    match ch {
+        '\\' => Some("\\\\"),
+        '\t' => Some("\\t"),
+        '\n' => Some("\\n"),
+        '\r' => Some("\\r"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zsh_escape() {
+        assert!(matches!(zsh("plain.name"), Cow::Borrowed("plain.name")));
+        assert_eq!(zsh("a:b"), "a\\:b");
+        assert_eq!(zsh("a b"), "a\\ b");
+        assert_eq!(zsh("a (2024)"), "a\\ \\(2024\\)");
+        assert_eq!(zsh("100%"), "100%%");
+        assert_eq!(zsh("a\\b"), "a\\\\b");
+        assert_eq!(zsh("äö:"), "äö\\:");
+    }
+
+    #[test]
+    fn porcelain_escape() {
+        assert!(matches!(porcelain("a b:c"), Cow::Borrowed("a b:c")));
+        assert_eq!(porcelain("a\tb\nc"), "a\\tb\\nc");
+        assert_eq!(porcelain("a\\b"), "a\\\\b");
+    }
+}
@@ -9,33 +9,47 @@ pub mod console;
 pub mod db;
 pub mod display;
 pub mod errors;
+pub mod escape;
+pub mod template;
 pub mod torrent;
 
 use base64::Engine as _;
 use db::DBSqlite;
 use std::borrow::Borrow;
+use std::collections::BTreeMap;
 use std::convert::TryFrom as _;
-use std::io::Write;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::iter::Iterator;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::SystemTime;
-use termcolor::WriteColor;
+use termcolor::{NoColor, WriteColor};
 
-use display::ByteSize;
+use display::{ActionName, ByteSize};
 use magnet_uri::MagnetURI;
+use notify_rust::Urgency;
 use torrent::Torrent as TorrentInfo;
 use tracing::{event, instrument, span, Level};
-use transmission_rpc::types::{Torrent, TorrentStatus};
+use transmission_rpc::types::{ErrorType, Torrent, TorrentStatus};
 use url::Url;
 
 use client::TorrentAction;
-use client::{Client, QueryCmd, TorrentAddArgs, TorrentAdded, TorrentCli, TorrentFilter};
-use console::{Action, ConfirmAction, Console, Logger, ReadLine, View};
+use client::{
+    Client, DedupeBy, GroupBy, OutputFormat, PriorityLevel, QueryCmd, Sort, TorrentAddArgs,
+    TorrentAdded, TorrentCli, TorrentFilter,
+};
+use config::CopydirName;
+use console::{
+    Action, ConfirmAction, Console, Dbus, Logger, NotifyView, QueryWriter, ReadLine, View,
+};
 #[cfg(feature = "sqlite")]
 use db::DB;
 use errors::*;
 
+/// How long to wait for the peers to send the metadata of a magnet
+const METADATA_TIMEOUT: std::time::Duration = std::time::Duration::from_mins(2);
+
 pub struct TorrentAddResult {
     pub response: TorrentAdded,
     pub exists: Option<u64>,
@@ -48,22 +62,144 @@ pub struct TorrentAddResult {
 pub struct AddArgs<'a> {
     pub location: &'a TorrentLoc,
     pub dldir: Option<&'a PathBuf>,
-    pub use_existing: bool,
+    /// The configured `default_use_existing` when None
+    pub use_existing: Option<bool>,
+    /// Refuse to add unless the info hash is this
+    pub expect_hash: Option<&'a str>,
+    /// Refuse to add instead of adding paused if there is not enough space at all
+    pub no_overcommit: bool,
+    /// Labels in addition to the configured ones
+    pub labels: &'a [String],
+    pub on_duplicate: OnDuplicate,
+    /// Left to transmission when None
+    pub peer_limit: Option<i64>,
+    pub priority: Option<PriorityLevel>,
+    /// Headers of the torrent file download as `Name: value`, after the configured ones
+    pub headers: &'a [String],
+}
+
+/// What to do when the torrent has already been added
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq)]
+pub enum OnDuplicate {
+    /// Only report it
+    #[default]
+    Log,
+    /// Print its id
+    Print,
+    /// Remove the existing torrent leaving its data in place
+    Rm,
+    /// Stop the existing torrent
+    Stop,
+    /// Fail. The torrent file is kept like on the other failures to add.
+    Error,
 }
 
 pub struct Trmv<C: TorrentCli, V: View> {
     pub client: Client<C>,
     pub view: V,
     pub copydir: Option<PathBuf>,
+    pub copydir_name: CopydirName,
+    pub add_labels: Vec<String>,
     pub base_dir: PathBuf,
     pub dldirs: Vec<PathBuf>,
+    /// Dldirs by a part of the tracker url
+    pub tracker_dldir: BTreeMap<String, PathBuf>,
+    /// Whether the added torrents already have files in the dldir unless told otherwise
+    pub default_use_existing: bool,
+    /// Headers of the torrent file downloads by host
+    pub http_headers: BTreeMap<String, Vec<String>>,
     pub quota: u64,
+    /// Refuse instead of adding paused when over the quota
+    pub strict_quota: bool,
     pub safe_space: u64,
     #[cfg(feature = "sqlite")]
     pub db: DBSqlite,
+    /// Look up whether a torrent has been added before
+    pub dedupe: bool,
+    /// Torrents added during this run that the daemon might not know the size of yet
+    pub projected: Vec<Projected>,
+    /// Space of the download directories as first queried in this run, with the number of
+    /// `projected` torrents accounted in it
+    pub space_cache: BTreeMap<PathBuf, (DirSpace, usize)>,
+}
+
+/// Space accounting of a download directory
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct DirSpace {
+    /// As reported by transmission
+    pub free: i64,
+    /// Free space left once the torrents in the directory have finished
+    pub safe: i64,
+    /// Size of the torrents in the directory once they have finished
+    pub total_size: i64,
+}
+
+/// What happened to a torrent in `mv`
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveOutcome {
+    Moved,
+    /// The move script exits with 3 when the destination is low on space
+    NotEnoughSpace,
+    Failed,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct MoveResult {
+    pub hash: String,
+    pub name: String,
+    pub destination: PathBuf,
+    pub outcome: MoveOutcome,
+    /// Exit code of the move script. None if it was killed by a signal.
+    pub code: Option<i32>,
+}
+
+#[derive(Debug)]
+pub struct Projected {
+    pub hsh: String,
+    pub download_dir: PathBuf,
+    pub size: i64,
+    /// Counts toward the quota but not against the safe space
+    pub paused: bool,
+}
+
+/// An add that has passed the checks and only needs its rpc request
+#[derive(Debug)]
+struct PendingAdd {
+    add_args: TorrentAddArgs,
+    hsh: String,
+    left: i64,
+    total_size: i64,
+    exists: Option<u64>,
+    paused: bool,
+    /// Index into `Trmv::projected`
+    projected: usize,
+    /// Contents of the torrent file for the copydir
+    metainfo: Option<(Vec<u8>, TorrentInfo)>,
 }
 
 impl<C: TorrentCli, V: View> Trmv<C, V> {
+    // An explicit dldir wins
+    fn tracker_dldir(
+        &mut self,
+        dldir: Option<&PathBuf>,
+        trackers: &[String],
+    ) -> Result<Option<PathBuf>> {
+        if dldir.is_some() {
+            return Ok(None);
+        }
+        let Some((key, dir)) = pick_tracker_dldir(&self.tracker_dldir, trackers) else {
+            return Ok(None);
+        };
+        print_info!(
+            self.view.log(),
+            "dldir {} for tracker {}",
+            dir.display(),
+            key
+        )?;
+        Ok(Some(dir.clone()))
+    }
+
     fn get_safe_space_and_dldir(
         &mut self,
         dldir: Option<&PathBuf>,
@@ -82,6 +218,45 @@ impl<C: TorrentCli, V: View> Trmv<C, V> {
                 download_dir.push(d);
             }
         }
+        let DirSpace {
+            safe: safe_space,
+            total_size,
+            ..
+        } = self.dir_space(&download_dir)?;
+
+        print_info!(
+            self.view.log(),
+            "safe space: {}, total_size: {} of quota {} in {}",
+            ByteSize(safe_space),
+            ByteSize(total_size),
+            ByteSize(self.quota),
+            download_dir.display(),
+        )?;
+
+        if !use_existing {
+            download_dir.push(hsh);
+        }
+        print_debug!(self.view.log(), "download_dir: {}", download_dir.display()).context("log")?;
+
+        Ok((safe_space, total_size, download_dir))
+    }
+
+    /// Space accounting of a download directory. The safe space is what is left once the
+    /// torrents in it have finished. The daemon is only asked once per directory, the torrents
+    /// added after that are subtracted locally.
+    pub fn dir_space(&mut self, download_dir: &Path) -> Result<DirSpace> {
+        if let Some(&(mut space, accounted)) = self.space_cache.get(download_dir) {
+            for p in &self.projected[accounted..] {
+                if p.download_dir.starts_with(download_dir) {
+                    print_debug!(self.view.log(), "projected {}: {}", p.hsh, ByteSize(p.size))?;
+                    space.total_size += p.size;
+                    if !p.paused {
+                        space.safe -= p.size;
+                    }
+                }
+            }
+            return Ok(space);
+        }
         let f = self
             .client
             .free_space(download_dir.to_string_lossy().to_string())
@@ -93,9 +268,10 @@ impl<C: TorrentCli, V: View> Trmv<C, V> {
         let torrents = self.client.torrent_get(None, None)?;
         let mut total_size = 0;
         let mut safe_space = free_space;
+        let mut accounted = std::collections::HashSet::new();
         for t in torrents {
             if Path::new(t.download_dir.as_ref().context("torrent without dldir")?)
-                .starts_with(&download_dir)
+                .starts_with(download_dir)
             {
                 let mut allocated_size = 0;
                 for file in t.files.as_ref().context("undefined files")? {
@@ -143,26 +319,60 @@ impl<C: TorrentCli, V: View> Trmv<C, V> {
                 } else {
                     safe_space -= left_until_done;
                 }
+
+                if final_size > 0 {
+                    if let Some(hsh) = t.hash_string {
+                        accounted.insert(hsh);
+                    }
+                }
             }
         }
 
-        print_info!(
-            self.view.log(),
-            "safe space: {}, total_size: {} in {}",
-            ByteSize(safe_space),
-            ByteSize(total_size),
-            download_dir.display(),
-        )?;
+        // Magnets added earlier in the same batch have no files yet
+        for p in &self.projected {
+            if p.download_dir.starts_with(download_dir) && !accounted.contains(&p.hsh) {
+                print_debug!(self.view.log(), "projected {}: {}", p.hsh, ByteSize(p.size))?;
+                total_size += p.size;
+                if !p.paused {
+                    safe_space -= p.size;
+                }
+            }
+        }
 
-        if !use_existing {
-            download_dir.push(hsh);
+        let space = DirSpace {
+            free: free_space,
+            safe: safe_space,
+            total_size,
+        };
+        self.space_cache
+            .insert(download_dir.to_path_buf(), (space, self.projected.len()));
+        Ok(space)
+    }
+
+    /// Space accounting of every configured download directory
+    pub fn space(&mut self) -> Result<Vec<(PathBuf, DirSpace)>> {
+        let mut dirs = Vec::new();
+        for dldir in self.dldirs.clone() {
+            let download_dir = self.base_dir.join(&dldir);
+            let space = self.dir_space(&download_dir)?;
+            dirs.push((download_dir, space));
         }
-        print_debug!(self.view.log(), "download_dir: {}", download_dir.display()).context("log")?;
+        Ok(dirs)
+    }
 
-        Ok((safe_space, total_size, download_dir))
+    /// Don't look up or record the torrents added in this run
+    pub fn disable_dedupe(&mut self) {
+        self.dedupe = false;
+        #[cfg(feature = "sqlite")]
+        {
+            self.db = DBSqlite::new(None);
+        }
     }
 
     fn check_existing(&mut self, hsh: &str) -> Result<Option<u64>> {
+        if !self.dedupe {
+            return Ok(None);
+        }
         let exists_copydir = if let Some(ref copydir) = self.copydir {
             Self::check_existing_copydir(copydir, hsh)?
         } else {
@@ -179,164 +389,710 @@ impl<C: TorrentCli, V: View> Trmv<C, V> {
     }
 
     fn check_existing_copydir(copydir: &Path, hsh: &str) -> Result<Option<u64>> {
-        let mut existing = copydir.join(hsh);
-        existing.set_extension("torrent");
-        match existing.metadata() {
-            Err(error) => match error.kind() {
-                std::io::ErrorKind::NotFound => Ok(None),
-                _ => Err(anyhow!(error)).context("Copydir"),
-            },
-            Ok(meta) => Ok(Some(
-                meta.modified()?
-                    .duration_since(SystemTime::UNIX_EPOCH)?
-                    .as_secs(),
-            )),
+        // Always looked up by hash regardless of the naming scheme
+        for dir in [copydir.to_path_buf(), copydir.join(".hash")] {
+            match hash_torrent_path(&dir, hsh).metadata() {
+                Err(error) => match error.kind() {
+                    std::io::ErrorKind::NotFound => {}
+                    _ => return Err(anyhow!(error)).context("Copydir"),
+                },
+                Ok(meta) => {
+                    return Ok(Some(
+                        meta.modified()?
+                            .duration_since(SystemTime::UNIX_EPOCH)?
+                            .as_secs(),
+                    ))
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // The hash named copies are left to the move script
+    fn save_copydir(&mut self, content: &[u8], torrent: &TorrentInfo) -> Result<()> {
+        let Some(ref copydir) = self.copydir else {
+            return Ok(());
+        };
+        if self.copydir_name != CopydirName::Name {
+            return Ok(());
+        }
+        let name = String::from_utf8_lossy(&torrent.name).replace('/', "_");
+        let mut path = copydir.join(format!("{name}.torrent"));
+        if path.exists() {
+            path = copydir.join(format!("{name} ({:.8}).torrent", torrent.info_hash));
+        }
+        if path.exists() {
+            print_debug!(self.view.log(), "{} exists", path.display())?;
+            return Ok(());
+        }
+        print_debug!(self.view.log(), "saving {}", path.display())?;
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, content).context("Copydir")?;
+        std::fs::rename(&tmp, &path).context("Copydir")?;
+
+        let hash_dir = copydir.join(".hash");
+        std::fs::create_dir_all(&hash_dir).context("Copydir")?;
+        let link = hash_torrent_path(&hash_dir, &torrent.info_hash);
+        if !link.exists() {
+            std::fs::hard_link(&path, &link).context("Copydir")?;
         }
+        Ok(())
     }
 
     // Breaks completion
-    #[instrument(err, level = "trace", skip(self))]
+    // The url may carry a passkey
+    #[instrument(err, level = "trace", skip(self, args))]
     pub fn add(&mut self, args: &AddArgs) -> Result<()> {
+        let mut pending = self.prepare_add(args)?;
+        let response = self
+            .client
+            .torrent_add(std::mem::take(&mut pending.add_args));
+        self.finish_add(args, pending, response)
+    }
+
+    /// Adds the torrents sending up to `1 + helpers.len()` add requests at a time, one per
+    /// client. The checks stay sequential and the torrents waiting for their request are
+    /// accounted as added. Stops after the batch where an add failed for other than
+    /// `NothingToDo`, so there can be fewer results than `args`.
+    pub fn add_many(&mut self, args: &[AddArgs], helpers: &mut [Client<C>]) -> Vec<Result<()>>
+    where
+        C: Send,
+    {
+        let mut results = Vec::with_capacity(args.len());
+        for batch in args.chunks(helpers.len() + 1) {
+            let mut prepared = Vec::with_capacity(batch.len());
+            for a in batch {
+                prepared.push(self.prepare_add(a));
+            }
+            let mut responses = std::thread::scope(|s| {
+                let handles: Vec<_> = prepared
+                    .iter_mut()
+                    .filter_map(|p| p.as_mut().ok())
+                    .zip(std::iter::once(&mut self.client).chain(helpers.iter_mut()))
+                    .map(|(pending, client)| {
+                        let add_args = std::mem::take(&mut pending.add_args);
+                        s.spawn(move || client.torrent_add(add_args))
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|h| {
+                        h.join()
+                            .unwrap_or_else(|_| Err(anyhow!("add request panicked")))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .into_iter();
+            let mut failed = false;
+            for (a, p) in batch.iter().zip(prepared) {
+                let res = p.and_then(|pending| {
+                    let response = responses.next().context("missing add response")?;
+                    self.finish_add(a, pending, response)
+                });
+                if let Err(ref err) = res {
+                    failed |= err.downcast_ref::<NothingToDo>().is_none();
+                }
+                results.push(res);
+            }
+            if failed {
+                break;
+            }
+        }
+        results
+    }
+
+    /// Checks the torrent and picks its download directory, everything short of the add request
+    fn prepare_add(&mut self, args: &AddArgs) -> Result<PendingAdd> {
         let location = args.location;
         let dldir = args.dldir;
-        let use_existing = args.use_existing;
+        let use_existing = args.use_existing.unwrap_or(self.default_use_existing);
         match location {
             TorrentLoc::Path(path) => {
                 let content = std::fs::read(path)?;
-                let torrent = TorrentInfo::from_bytes(&content).context("TorrentInfo")?;
-                let hsh = torrent.info_hash;
-                event!(Level::DEBUG, "got hsh [{hsh}]");
-                print_debug!(self.view.log(), "info hash: {}", hsh)?;
-                let exists = self.check_existing(&hsh)?;
-                if let Some(time) = exists {
-                    if !self.view.ask_existing(&torrent.name, time)? {
-                        bail!(NothingToDo("Nothing to do"));
-                    }
-                }
-                let (safe_space, total_size, download_dir) =
-                    self.get_safe_space_and_dldir(dldir, &hsh, use_existing)?;
-                print_debug!(self.view.log(), "torrent length: {}", torrent.length)?;
-                let would_be_left =
-                    safe_space - i64::try_from(torrent.length).context("overflow")?;
-                let would_be_size =
-                    total_size + i64::try_from(torrent.length).context("overflow")?;
-
-                self.add_torrent(
-                    TorrentAddArgs {
-                        download_dir: Some(download_dir.to_string_lossy().to_string()),
-                        metainfo: Some(base64::engine::general_purpose::STANDARD.encode(content)),
-                        paused: Some(u64::try_from(would_be_left).unwrap_or(0) < self.safe_space),
-                        ..TorrentAddArgs::default()
-                    },
-                    would_be_left,
-                    would_be_size,
-                    exists,
-                    &hsh,
-                )?;
-                std::fs::remove_file(path).context("remove_file")?;
+                self.prepare_metainfo(args, content)
+            }
+            TorrentLoc::Url(url) if url.scheme() != "magnet" => {
+                let content = self.fetch_torrent(url, args.headers)?;
+                self.prepare_metainfo(args, content)
             }
             TorrentLoc::Url(url) => {
-                let magnet = MagnetURI::from_str(url.as_str())
-                    .map_err(MagnetURIError)
-                    .context("Non magnet urls not supported yet")?;
+                let magnet = MagnetURI::from_str(url.as_str()).map_err(MagnetURIError)?;
                 if !magnet.is_strictly_valid() {
                     print_warn!(self.view.log(), "Not a strictly valid magnet link")?;
                 }
-                let mut hsh_tmp = magnet
-                    .iter_topics()
-                    .find_map(|h| match h {
-                        magnet_uri::Topic::BitTorrentInfoHash(hs) => Some(hs),
-                        _ => None,
-                    })
-                    .or_else(|| magnet.info_hash())
-                    .ok_or_else(|| {
-                        anyhow!("Magnet urls without any info hash are not supported")
-                    })?;
-
-                print_debug!(self.view.log(), "Magnet hsh before fixing: {}", &hsh_tmp)
-                    .context("log")?;
-
-                let mut hsh_owned;
-                if hsh_tmp.len() != 40 {
-                    let b32 = base32::decode(base32::Alphabet::Rfc4648 { padding: false }, hsh_tmp)
-                        .ok_or_else(|| anyhow!("Invalid hash"))?;
-                    hsh_owned = hex::encode(b32);
-                    hsh_tmp = &hsh_owned;
-                }
-                hsh_owned = hsh_tmp.to_lowercase();
+                let MagnetInfo {
+                    hsh: hsh_owned,
+                    name,
+                    length,
+                    trackers,
+                } = magnet_info(url, &magnet)?;
+                print_debug!(self.view.log(), "Magnet hsh: {}", &hsh_owned).context("log")?;
 
                 let exists = self.check_existing(&hsh_owned)?;
                 if let Some(time) = exists {
                     if !self
                         .view
-                        .ask_existing(magnet.name().unwrap_or("magnet").as_bytes(), time)?
+                        .ask_existing(name.as_deref().unwrap_or("magnet").as_bytes(), time)?
                     {
                         bail!(NothingToDo("Nothing to do"));
                     }
                 }
 
-                let (safe_space, total_size, download_dir) =
-                    self.get_safe_space_and_dldir(dldir, &hsh_owned, use_existing)?;
-                // about size as we don't know
-                let would_be_left = safe_space - 5 * 1024 * 1024 * 1024;
-                let would_be_size = total_size + 5 * 1024 * 1024 * 1024;
-                self.add_torrent(
+                let tracker_dir = self.tracker_dldir(dldir, &trackers)?;
+                let (safe_space, total_size, download_dir) = self.get_safe_space_and_dldir(
+                    dldir.or(tracker_dir.as_ref()),
+                    &hsh_owned,
+                    use_existing,
+                )?;
+                // about size if the magnet does not tell
+                let size = length.unwrap_or(MAGNET_SIZE_GUESS);
+                print_debug!(self.view.log(), "magnet length: {:?}", length)?;
+                let would_be_left = safe_space - size;
+                Self::check_overcommit(args.no_overcommit, would_be_left)?;
+                let would_be_size = total_size + size;
+                // the data is there already
+                let over_quota = exists.is_none() && self.check_quota(would_be_size)?;
+                self.pending_add(
                     TorrentAddArgs {
-                        paused: Some(u64::try_from(would_be_left).unwrap_or(0) < self.safe_space),
+                        paused: Some(
+                            u64::try_from(would_be_left).unwrap_or(0) < self.safe_space
+                                || over_quota,
+                        ),
                         download_dir: Some(download_dir.to_string_lossy().to_string()),
                         filename: Some(url.as_str().to_string()),
+                        labels: self.labels(args.labels),
+                        peer_limit: args.peer_limit,
+                        bandwidth_priority: args.priority.map(Into::into),
                         ..TorrentAddArgs::default()
                     },
                     would_be_left,
                     would_be_size,
+                    size,
                     exists,
-                    &hsh_owned,
-                )?;
+                    hsh_owned,
+                    None,
+                )
+            }
+        }
+    }
+
+    /// Checks the contents of a torrent file
+    fn prepare_metainfo(&mut self, args: &AddArgs, content: Vec<u8>) -> Result<PendingAdd> {
+        let dldir = args.dldir;
+        let use_existing = args.use_existing.unwrap_or(self.default_use_existing);
+        let torrent = TorrentInfo::from_bytes(&content).context("TorrentInfo")?;
+        let hsh = torrent.info_hash.clone();
+        event!(Level::DEBUG, "got hsh [{hsh}]");
+        print_debug!(self.view.log(), "info hash: {}", hsh)?;
+        for tracker in &torrent.trackers {
+            print_debug!(self.view.log(), "tracker: {}", tracker)?;
+        }
+        if torrent.skipped_trackers > 0 {
+            print_warn!(
+                self.view.log(),
+                "skipped {} invalid tracker entries",
+                torrent.skipped_trackers
+            )?;
+        }
+        if let Some(expected) = args.expect_hash {
+            if !expected.eq_ignore_ascii_case(&hsh) {
+                bail!(HashMismatch {
+                    expected: expected.to_lowercase(),
+                    actual: hsh,
+                });
+            }
+        }
+        let exists = self.check_existing(&hsh)?;
+        if let Some(time) = exists {
+            if !self.view.ask_existing(&torrent.name, time)? {
+                bail!(NothingToDo("Nothing to do"));
+            }
+        }
+        let tracker_dir = self.tracker_dldir(dldir, &torrent.trackers)?;
+        let (safe_space, total_size, download_dir) =
+            self.get_safe_space_and_dldir(dldir.or(tracker_dir.as_ref()), &hsh, use_existing)?;
+        print_debug!(self.view.log(), "torrent length: {}", torrent.length)?;
+        let size = i64::try_from(torrent.length).context("overflow")?;
+        let would_be_left = safe_space - size;
+        Self::check_overcommit(args.no_overcommit, would_be_left)?;
+        let would_be_size = total_size + size;
+        // the data is there already
+        let over_quota = exists.is_none() && self.check_quota(would_be_size)?;
+
+        self.pending_add(
+            TorrentAddArgs {
+                download_dir: Some(download_dir.to_string_lossy().to_string()),
+                metainfo: Some(base64::engine::general_purpose::STANDARD.encode(&content)),
+                labels: self.labels(args.labels),
+                paused: Some(
+                    u64::try_from(would_be_left).unwrap_or(0) < self.safe_space || over_quota,
+                ),
+                peer_limit: args.peer_limit,
+                bandwidth_priority: args.priority.map(Into::into),
+                ..TorrentAddArgs::default()
+            },
+            would_be_left,
+            would_be_size,
+            size,
+            exists,
+            hsh,
+            Some((content, torrent)),
+        )
+    }
+
+    fn fetch_torrent(&mut self, url: &Url, extra: &[String]) -> Result<Vec<u8>> {
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!("Only magnet and http(s) urls are supported");
+        }
+        let headers = pick_http_headers(&self.http_headers, url, extra)
+            .into_iter()
+            .map(parse_header)
+            .collect::<Result<reqwest::header::HeaderMap>>()?;
+        // only the names as the values are likely secrets
+        let names: Vec<&str> = headers
+            .keys()
+            .map(reqwest::header::HeaderName::as_str)
+            .collect();
+        print_debug!(
+            self.view.log(),
+            "fetching {} with headers: {:?}",
+            redact_url(url),
+            names
+        )?;
+        // the errors would show the url
+        let strip = |e: reqwest::Error| anyhow!(e.without_url());
+        let content = client::new_runtime()?.block_on(async {
+            let mut res = reqwest::Client::builder()
+                .timeout(FETCH_TIMEOUT)
+                .redirect(redirect_policy(!headers.is_empty()))
+                .build()
+                .map_err(strip)?
+                .get(url.clone())
+                .headers(headers)
+                .send()
+                .await
+                .map_err(strip)?
+                .error_for_status()
+                .map_err(strip)?;
+            let mut content = Vec::new();
+            while let Some(chunk) = res.chunk().await.map_err(strip)? {
+                if content.len() + chunk.len() > MAX_TORRENT_SIZE {
+                    bail!(
+                        "larger than the {} MiB allowed of a torrent file",
+                        MAX_TORRENT_SIZE / 1024 / 1024
+                    );
+                }
+                content.extend_from_slice(&chunk);
+            }
+            Ok(content)
+        });
+        content.with_context(|| format!("fetch torrent from {}", redact_url(url)))
+    }
+
+    fn handle_duplicate(&mut self, on_duplicate: OnDuplicate, id: i64, hsh: &str) -> Result<()> {
+        match on_duplicate {
+            // the view has reported it already
+            OnDuplicate::Log => {}
+            OnDuplicate::Print => self.view.print_duplicate_id(id)?,
+            OnDuplicate::Rm => {
+                self.client.torrent_remove(vec![hsh.to_string()], false)?;
+                print_info!(self.view.log(), "Removed the existing torrent {}", id)?;
+            }
+            OnDuplicate::Stop => {
+                self.client
+                    .torrent_action(vec![hsh.to_string()], TorrentAction::Stop)?;
+                print_info!(self.view.log(), "Stopped the existing torrent {}", id)?;
+            }
+            OnDuplicate::Error => bail!("Torrent already exists with id {}", id),
+        }
+        Ok(())
+    }
+
+    fn labels(&self, extra: &[String]) -> Option<Vec<String>> {
+        let mut labels = self.add_labels.clone();
+        for label in extra {
+            if !labels.contains(label) {
+                labels.push(label.clone());
             }
         }
+        (!labels.is_empty()).then_some(labels)
+    }
+
+    fn check_overcommit(no_overcommit: bool, would_be_left: i64) -> Result<()> {
+        if no_overcommit && would_be_left < 0 {
+            return Err(anyhow!(NotEnoughSpace)
+                .context(format!("{} more space needed", ByteSize(-would_be_left))));
+        }
         Ok(())
     }
 
-    fn add_torrent(
+    /// Whether the dldir would be over its quota. Fails instead with `strict_quota`.
+    fn check_quota(&mut self, would_be_size: i64) -> Result<bool> {
+        let over = u64::try_from(would_be_size).unwrap_or(0) > self.quota;
+        if over {
+            let msg = format!(
+                "{} would exceed the quota of {}",
+                ByteSize(would_be_size),
+                ByteSize(self.quota)
+            );
+            if self.strict_quota {
+                return Err(anyhow!(NotEnoughSpace).context(msg));
+            }
+            print_warn!(self.view.log(), "{}, adding paused", msg)?;
+        }
+        Ok(over)
+    }
+
+    /// Accounts the torrent as added already so that the space checks that follow see it
+    #[allow(clippy::too_many_arguments)]
+    fn pending_add(
         &mut self,
         add_args: TorrentAddArgs,
         left: i64,
         total_size: i64,
+        size: i64,
         exists: Option<u64>,
-        hsh: &str,
+        hsh: String,
+        metainfo: Option<(Vec<u8>, TorrentInfo)>,
+    ) -> Result<PendingAdd> {
+        let paused = add_args.paused.context("undefined paused")?;
+        let download_dir = add_args
+            .download_dir
+            .clone()
+            .context("undefined download_dir")?;
+        self.projected.push(Projected {
+            hsh: hsh.clone(),
+            download_dir: PathBuf::from(download_dir),
+            size,
+            paused,
+        });
+        Ok(PendingAdd {
+            add_args,
+            hsh,
+            left,
+            total_size,
+            exists,
+            paused,
+            projected: self.projected.len() - 1,
+            metainfo,
+        })
+    }
+
+    /// Reports the add request and does what is left after it
+    fn finish_add(
+        &mut self,
+        args: &AddArgs,
+        pending: PendingAdd,
+        response: Result<TorrentAdded>,
     ) -> Result<()> {
         let span = span!(Level::TRACE, "add_torrent");
         let _guard = span.enter();
 
-        let full = add_args.paused.context("undefined paused")?;
-        let response = self.client.torrent_add(add_args)?;
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                self.projected[pending.projected].size = 0;
+                return Err(err);
+            }
+        };
+        let hsh = pending.hsh;
         // TODO don't insert if it was found in the db
-        self.db.store(hsh)?;
-        match &response {
-            // TODO check hash returned matches above?
-            TorrentAdded::TorrentAdded { .. } | TorrentAdded::TorrentDuplicate { .. } => {
-                self.view.torrent_add_result(&TorrentAddResult {
-                    response,
-                    exists,
-                    full,
-                    left,
-                    total_size,
-                })
+        self.db.store(&hsh)?;
+        let duplicate = match &response {
+            TorrentAdded::TorrentAdded { .. } => None,
+            TorrentAdded::TorrentDuplicate { id, .. } => {
+                // the existing torrent is known to the daemon
+                self.projected[pending.projected].size = 0;
+                Some(id.context("undefined id")?)
             }
+        };
+        // TODO check hash returned matches above?
+        self.view.torrent_add_result(&TorrentAddResult {
+            response,
+            exists: pending.exists,
+            full: pending.paused,
+            left: pending.left,
+            total_size: pending.total_size,
+        })?;
+        if let Some(id) = duplicate {
+            self.handle_duplicate(args.on_duplicate, id, &hsh)?;
         }
+        // the copydir is a record of the added torrents like the db
+        if let (Some((content, torrent)), true) = (&pending.metainfo, self.dedupe) {
+            self.save_copydir(content, torrent)?;
+        }
+        if let TorrentLoc::Path(path) = args.location {
+            std::fs::remove_file(path).context("remove_file")?;
+        }
+        Ok(())
     }
 }
 
 // pub so that https://rust-embedded.github.io/book/design-patterns/hal/interoperability.html
 #[derive(Debug)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Trctl<T, C> {
     client: Client<T>,
     pub console: C,
     dldirs: Vec<PathBuf>,
     verify: bool,
+    prune_empty_dirs: bool,
+    remote_rmdir: bool,
+    follow_symlinks_on_rm: bool,
+    rmdir_non_empty: bool,
+    rm_confirm_threshold: usize,
     pub interactive: bool,
     pub dst_free_space_to_leave: u64,
     pub is_remote: bool,
+    /// Sends a single summary notification at the end of a command if set
+    pub notifier: Option<Dbus>,
+    /// Remembers the torrents shown by `query --new`
+    #[cfg(feature = "sqlite")]
+    pub db: DBSqlite,
+}
+
+/// Size assumed of a magnet link without the exact length
+const MAGNET_SIZE_GUESS: i64 = 5 * 1024 * 1024 * 1024;
+
+/// What adding needs of a magnet link
+#[derive(Debug, PartialEq)]
+struct MagnetInfo {
+    /// Lowercase hex
+    hsh: String,
+    /// The display name, decoded
+    name: Option<String>,
+    /// The exact length
+    length: Option<i64>,
+    trackers: Vec<String>,
+}
+
+fn magnet_info(url: &Url, magnet: &MagnetURI) -> Result<MagnetInfo> {
+    let hsh = magnet
+        .iter_topics()
+        .find_map(|h| match h {
+            magnet_uri::Topic::BitTorrentInfoHash(hs) => Some(hs),
+            _ => None,
+        })
+        .or_else(|| magnet.info_hash())
+        .ok_or_else(|| anyhow!("Magnet urls without any info hash are not supported"))?;
+    let hsh = if hsh.len() == 40 {
+        hex::decode(hsh).map_err(|_| anyhow!("Invalid hash"))?;
+        hsh.to_lowercase()
+    } else {
+        let b32 = base32::decode(base32::Alphabet::Rfc4648 { padding: false }, hsh)
+            .ok_or_else(|| anyhow!("Invalid hash"))?;
+        hex::encode(b32)
+    };
+    // the query pairs are percent decoded
+    let param = |key: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.into_owned())
+    };
+    Ok(MagnetInfo {
+        hsh,
+        name: param("dn"),
+        length: param("xl")
+            .and_then(|xl| xl.parse().ok())
+            .filter(|&xl: &i64| xl > 0),
+        trackers: url
+            .query_pairs()
+            .filter(|(k, _)| k == "tr")
+            .map(|(_, v)| v.into_owned())
+            .collect(),
+    })
+}
+
+/// The output the query options ask for. `quiet` leaves out the table like elsewhere.
+fn write_query<W: WriteColor>(
+    writer: &QueryWriter,
+    out: &mut W,
+    qcmd: &QueryCmd,
+    torrents: &[Torrent],
+    quiet: bool,
+) -> Result<()> {
+    if let Some(ref template) = qcmd.template {
+        return writer.templated(out, torrents, template);
+    }
+    if qcmd.porcelain {
+        return writer.porcelain(out, torrents);
+    }
+    if qcmd.names_only || qcmd.completion_format {
+        return writer.names(out, torrents, qcmd.completion_format, qcmd.print0);
+    }
+    match qcmd.group_by {
+        None if quiet => Ok(()),
+        None => writer.filtered(
+            out,
+            torrents,
+            !qcmd.no_header,
+            qcmd.swarm,
+            qcmd.seed_time || matches!(qcmd.sort, Some(Sort::SeedTime)),
+        ),
+        Some(GroupBy::Dldir) => writer.grouped(out, torrents),
+    }
+}
+
+/// `.<name>.tmp` next to the query output
+fn output_tmp_path(path: &Path) -> Result<PathBuf> {
+    let name = path
+        .file_name()
+        .with_context(|| format!("no file name in {}", path.display()))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(name);
+    tmp_name.push(".tmp");
+    Ok(path.with_file_name(tmp_name))
+}
+
+/// `<dir>/<hash>.torrent`
+fn hash_torrent_path(dir: &Path, hsh: &str) -> PathBuf {
+    let mut path = dir.join(hsh);
+    path.set_extension("torrent");
+    path
+}
+
+/// Keeps the newest torrent of each name where the first one of the name was. The names of the
+/// kept torrents get the count of the dropped ones, e.g. ` (x3)`.
+fn dedupe_by_name(torrents: Vec<Torrent>) -> Vec<Torrent> {
+    let mut groups: Vec<(Torrent, usize)> = Vec::with_capacity(torrents.len());
+    let mut by_name: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    for tor in torrents {
+        // torrents without a name are not duplicates of each other
+        let Some(name) = tor.name.clone() else {
+            groups.push((tor, 1));
+            continue;
+        };
+        if let Some(&i) = by_name.get(&name) {
+            let (kept, count) = &mut groups[i];
+            *count += 1;
+            if tor.added_date > kept.added_date {
+                *kept = tor;
+            }
+        } else {
+            by_name.insert(name, groups.len());
+            groups.push((tor, 1));
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(mut tor, count)| {
+            if count > 1 {
+                tor.name = tor.name.map(|name| format!("{name} (x{count})"));
+            }
+            tor
+        })
+        .collect()
+}
+
+/// The configured headers of the host of the url followed by the extra ones
+fn pick_http_headers<'a>(
+    http_headers: &'a BTreeMap<String, Vec<String>>,
+    url: &Url,
+    extra: &'a [String],
+) -> Vec<&'a str> {
+    url.host_str()
+        .and_then(|host| http_headers.get(host))
+        .into_iter()
+        .flatten()
+        .chain(extra)
+        .map(String::as_str)
+        .collect()
+}
+
+/// Renames or copies across file systems. The parent directories are created.
+fn move_file(src: &Path, dst: &Path) -> Result<()> {
+    if let Some(parent) = dst.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("creating {}", parent.display()))?;
+    }
+    if std::fs::rename(src, dst).is_ok() {
+        return Ok(());
+    }
+    let mut tmp = dst.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::copy(src, &tmp).with_context(|| format!("copying {}", src.display()))?;
+    std::fs::rename(&tmp, dst).with_context(|| format!("renaming {}", dst.display()))?;
+    std::fs::remove_file(src).with_context(|| format!("removing {}", src.display()))?;
+    Ok(())
+}
+
+/// Largest torrent file fetched
+const MAX_TORRENT_SIZE: usize = 10 * 1024 * 1024;
+const FETCH_TIMEOUT: std::time::Duration = std::time::Duration::from_mins(1);
+
+/// Without the query and fragment which may carry a passkey
+fn redact_url(url: &Url) -> String {
+    let port = url.port().map(|p| format!(":{p}")).unwrap_or_default();
+    format!(
+        "{}://{}{port}{}",
+        url.scheme(),
+        url.host_str().unwrap_or_default(),
+        url.path()
+    )
+}
+
+/// Follows redirects within the host. The headers were picked for the host so a redirect to
+/// another one fails instead of sending them there.
+fn redirect_policy(has_headers: bool) -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(move |attempt| {
+        let next = attempt.url().host_str().map(str::to_owned);
+        let first = attempt
+            .previous()
+            .first()
+            .and_then(|u| u.host_str())
+            .map(str::to_owned);
+        if attempt.previous().len() > 10 {
+            attempt.error("too many redirects")
+        } else if has_headers && next != first {
+            attempt.error(format!(
+                "redirected to {}, not sending the headers to another host",
+                next.unwrap_or_default()
+            ))
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+/// `Name: value` as a header that is hidden from debug output
+fn parse_header(
+    header: &str,
+) -> Result<(reqwest::header::HeaderName, reqwest::header::HeaderValue)> {
+    let (name, value) = header
+        .split_once(':')
+        .ok_or_else(|| anyhow!("header is not of the form 'Name: value'"))?;
+    let name = reqwest::header::HeaderName::from_str(name.trim())
+        .with_context(|| format!("invalid header name {}", name.trim()))?;
+    let mut value = reqwest::header::HeaderValue::from_str(value.trim())
+        .with_context(|| format!("invalid value of header {name}"))?;
+    value.set_sensitive(true);
+    Ok((name, value))
+}
+
+/// A shell like glob where `*` matches any run of characters, `/` included, and `?` any single
+/// one
+fn glob_regex(glob: &str) -> Result<regex::Regex> {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    regex::Regex::new(&pattern).with_context(|| format!("invalid glob {glob}"))
+}
+
+/// The first configured dldir whose key is a part of one of the tracker urls
+fn pick_tracker_dldir<'a>(
+    tracker_dldir: &'a BTreeMap<String, PathBuf>,
+    trackers: &[String],
+) -> Option<(&'a String, &'a PathBuf)> {
+    tracker_dldir
+        .iter()
+        .find(|(key, _)| trackers.iter().any(|t| t.contains(key.as_str())))
 }
 
 #[derive(Debug)]
@@ -347,6 +1103,8 @@ pub enum TorrentLoc {
 
 impl<T: TorrentCli, O: WriteColor, I: ReadLine> Trctl<T, Console<O, I>> {
     pub fn erase(&mut self, mut qcmd: QueryCmd, delete_data: bool) -> Result<()> {
+        let mut removed = 0;
+        let mut freed = 0;
         if qcmd.files {
             let torrents: Vec<Torrent> =
                 self.client.torrent_get(None, None).context("torrent_get")?;
@@ -368,10 +1126,15 @@ impl<T: TorrentCli, O: WriteColor, I: ReadLine> Trctl<T, Console<O, I>> {
                     }
                     Ok(filtered_iter) => filtered_iter.collect(),
                 };
+                self.check_matches_all(&qcmd, filtered.len(), torrents.len())?;
                 match Self::selectids(&mut self.console, &filtered, None, self.interactive) {
                     Ok(selected) => {
-                        self.erase_selected(&selected, &torrents, delete_data)
+                        self.check_rm_count(selected.len())?;
+                        let (count, size) = self
+                            .erase_selected(&selected, &torrents, delete_data)
                             .context("erase_selected")?;
+                        removed += count;
+                        freed += size;
                     }
                     Err(err) => {
                         if let Some(NothingToDo(msg)) = err.downcast_ref::<NothingToDo>() {
@@ -384,15 +1147,39 @@ impl<T: TorrentCli, O: WriteColor, I: ReadLine> Trctl<T, Console<O, I>> {
                 self.console.log().pop_indent();
             }
         } else {
-            let filtered: Vec<Torrent> = self
-                .client
-                .torrent_query_sort(None, &qcmd)
-                .context("query")?;
+            let (filtered, total) = self.query_sort_total(&qcmd).context("query")?;
+            self.check_matches_all(&qcmd, filtered.len(), total)?;
             let selected = Self::selectids(&mut self.console, &filtered, None, self.interactive)
                 .context("selectids")?;
-            self.erase_selected(&selected, &filtered, delete_data)
+            self.check_rm_count(selected.len())?;
+            (removed, freed) = self
+                .erase_selected(&selected, &filtered, delete_data)
                 .context("erase_selected")?;
         }
+        if delete_data {
+            if removed > 0 {
+                print_info!(self.console.log(), "freed ~{}", ByteSize(freed))?;
+            }
+            self.notify_summary(
+                removed,
+                &format!("Removed {removed} torrents, freed {}", ByteSize(freed)),
+            )
+        } else {
+            self.notify_summary(removed, &format!("Erased {removed} torrents"))
+        }
+    }
+
+    /// Nothing is sent when nothing was affected
+    fn notify_summary(&mut self, affected: usize, summary: &str) -> Result<()> {
+        if affected == 0 {
+            return Ok(());
+        }
+        if let Some(ref notifier) = self.notifier {
+            // The command itself succeeded so a missing notification daemon is not fatal
+            if let Err(err) = notifier.notify(Urgency::Normal, summary, None) {
+                print_warn!(self.console.log(), "{:#}", err)?;
+            }
+        }
         Ok(())
     }
 
@@ -409,6 +1196,57 @@ impl<T: TorrentCli, O: WriteColor, I: ReadLine> Trctl<T, Console<O, I>> {
     //    }
     //}
 
+    // The first request of a command is where a flaky connection shows up
+    fn query_sort_total(&mut self, qcmd: &QueryCmd) -> Result<(Vec<Torrent>, usize)> {
+        let mut count = 1;
+        loop {
+            match self.client.torrent_query_sort_total(None, qcmd) {
+                Err(err)
+                    if self.interactive
+                        && count < 3
+                        && err.downcast_ref::<RpcConnection>().is_some() =>
+                {
+                    count += 1;
+                    if !self.console.ask_retry(&err)? {
+                        return Err(err);
+                    }
+                }
+                res => return res,
+            }
+        }
+    }
+
+    fn query_sort(&mut self, qcmd: &QueryCmd) -> Result<Vec<Torrent>> {
+        Ok(self.query_sort_total(qcmd)?.0)
+    }
+
+    // An empty query selects everything so make sure that was meant. Clean has its own criteria.
+    fn check_matches_all(&mut self, qcmd: &QueryCmd, matched: usize, total: usize) -> Result<()> {
+        if qcmd.cleanable || total < 2 || matched < total {
+            return Ok(());
+        }
+        if self.interactive {
+            print_warn!(self.console.log(), "matches all {} torrents", total)?;
+        } else if !qcmd.i_really_mean_it {
+            bail!(
+                "Query matches all {} torrents, use --i-really-mean-it if that is intended",
+                total
+            );
+        }
+        Ok(())
+    }
+
+    // Answering all is too easy to do by accident for a mass delete
+    fn check_rm_count(&mut self, count: usize) -> Result<()> {
+        if !self.interactive || count <= self.rm_confirm_threshold {
+            return Ok(());
+        }
+        if !self.console.confirm_count(count, "remove")? {
+            bail!(NothingToDo("Count not confirmed"));
+        }
+        Ok(())
+    }
+
     fn selectids<TOR: Borrow<Torrent>>(
         console: &mut Console<O, I>,
         torrents: &[TOR],
@@ -430,14 +1268,15 @@ impl<T: TorrentCli, O: WriteColor, I: ReadLine> Trctl<T, Console<O, I>> {
         }
     }
 
+    // Returns the count of removed torrents and their downloaded size
     fn erase_selected(
         &mut self,
         selected: &[usize],
         torrents: &[Torrent],
         delete_data: bool,
-    ) -> Result<()> {
+    ) -> Result<(usize, i64)> {
         if selected.is_empty() {
-            return Ok(());
+            return Ok((0, 0));
         }
 
         let msg = if delete_data { "rm" } else { "erase" };
@@ -464,6 +1303,7 @@ impl<T: TorrentCli, O: WriteColor, I: ReadLine> Trctl<T, Console<O, I>> {
         self.client.torrent_remove(ids, delete_data)?;
 
         if delete_data {
+            let mut skipped_remote = 0;
             let (_, mut errors): (Vec<_>, Vec<_>) = selected
                 .iter()
                 .map(|&i| -> Result<()> {
@@ -475,31 +1315,89 @@ impl<T: TorrentCli, O: WriteColor, I: ReadLine> Trctl<T, Console<O, I>> {
                     let h = it.hash_string.as_ref().ok_or_else(|| anyhow!("no hash"))?;
                     let p = std::path::Path::new(d);
                     if p.file_name() == Some(std::ffi::OsStr::new(h)) {
-                        if self.is_remote {
-                            print_info!(
+                        if self.is_remote && !self.remote_rmdir {
+                            print_debug!(
                                 self.console.log(),
                                 "not removing the hash dir of a remote torrent {}",
                                 d
                             )?;
+                            skipped_remote += 1;
                             return Ok(());
                         }
                         print_info!(self.console.log(), "rmdir {}", d)?;
-                        std::fs::remove_dir(p).or_else(|e| {
+                        let res = if p.is_symlink() {
+                            if self.follow_symlinks_on_rm {
+                                // the target is not ours to remove
+                                std::fs::remove_file(p).map_err(|e| anyhow!(e))
+                            } else {
+                                Err(anyhow!(
+                                    "{} is a symlink, set follow_symlinks_on_rm to remove the link",
+                                    d
+                                ))
+                            }
+                        } else if self.rmdir_non_empty {
+                            std::fs::remove_dir_all(p).map_err(|e| anyhow!(e))
+                        } else {
+                            std::fs::remove_dir(p).map_err(|e| anyhow!(e))
+                        };
+                        res.or_else(|e| {
                             print_error!(self.console.log(), "{}", e)?;
-                            Err(e.into())
-                        })
+                            Err(e)
+                        })?;
+                        if self.prune_empty_dirs {
+                            self.prune_empty_parents(p)?;
+                        }
+                        Ok(())
                     } else {
                         Ok(())
                     }
                 })
                 .partition(Result::is_ok);
+            if skipped_remote > 0 {
+                print_info!(
+                    self.console.log(),
+                    "skipped rmdir for {} remote torrents",
+                    skipped_remote
+                )?;
+            }
             if let Some(Err(res)) = errors.pop() {
                 return Err(res);
             }
         }
 
-        Ok(())
-    }
+        let size = selected
+            .iter()
+            .filter_map(|&i| {
+                let tor = &torrents[i];
+                // the completed bytes of the files are exact also for unwanted files
+                if let Some(ref files) = tor.files {
+                    return Some(files.iter().map(|f| f.bytes_completed).sum());
+                }
+                let size_when_done = tor.size_when_done.filter(|&x| x >= 0)?;
+                let left_until_done = tor.left_until_done.filter(|&x| x >= 0)?;
+                Some(size_when_done - left_until_done)
+            })
+            .sum();
+        Ok((selected.len(), size))
+    }
+
+    // Remove the empty parents of a removed directory up to but not including the dldir it is in
+    fn prune_empty_parents(&mut self, dir: &Path) -> Result<()> {
+        let mut parent = dir.parent();
+        while let Some(d) = parent {
+            let in_dldir = self.dldirs.iter().any(|dl| d.starts_with(dl) && d != dl);
+            if !in_dldir || !d.starts_with(&self.console.base_dir) {
+                break;
+            }
+            if std::fs::read_dir(d)?.next().is_some() {
+                break;
+            }
+            print_info!(self.console.log(), "rmdir {}", d.display())?;
+            std::fs::remove_dir(d)?;
+            parent = d.parent();
+        }
+        Ok(())
+    }
 
     //fn flatten<'n, X>(torrents: &'n[X], selected: &'n[usize]) -> impl Iterator<Item = &'n X>
     //{
@@ -526,13 +1424,135 @@ impl<T: TorrentCli, O: WriteColor, I: ReadLine> Trctl<T, Console<O, I>> {
     }
 
     pub fn query(&mut self, qcmd: &QueryCmd) -> Result<()> {
-        let torrents = self.client.torrent_query_sort(None, qcmd)?;
-        self.console.print_filtered(&torrents)
+        let Some(ref path) = qcmd.output else {
+            return self.query_to(qcmd, None::<&mut NoColor<File>>);
+        };
+        // fail on a bad path before talking to the server but keep an existing file until the
+        // results are in
+        let tmp = output_tmp_path(path)?;
+        let mut file = NoColor::new(BufWriter::new(
+            File::create(&tmp).with_context(|| format!("creating {}", tmp.display()))?,
+        ));
+        let res = self.query_to(qcmd, Some(&mut file)).and_then(|()| {
+            file.flush()?;
+            std::fs::rename(&tmp, path).with_context(|| format!("renaming to {}", path.display()))
+        });
+        if res.is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+        res
+    }
+
+    // The console when no file is given
+    fn query_to<W: WriteColor>(&mut self, qcmd: &QueryCmd, file: Option<&mut W>) -> Result<()> {
+        let torrents = if qcmd.names_only || qcmd.completion_format {
+            // meant for piping so no matches is not an error
+            match self.query_sort(qcmd) {
+                Err(err) if err.downcast_ref::<NoMatches>().is_some() => Vec::new(),
+                res => res?,
+            }
+        } else {
+            self.query_sort(qcmd)?
+        };
+        let torrents = match qcmd.dedupe_by {
+            None => torrents,
+            Some(DedupeBy::Name) => dedupe_by_name(torrents),
+        };
+        if qcmd.new {
+            let torrents = self.unseen(torrents)?;
+            if torrents.is_empty() && !(qcmd.names_only || qcmd.completion_format) {
+                bail!(NoMatches);
+            }
+            self.print_query(qcmd, &torrents, file)?;
+            return self.mark_seen(&torrents);
+        }
+        self.print_query(qcmd, &torrents, file)
+    }
+
+    fn print_query<W: WriteColor>(
+        &mut self,
+        qcmd: &QueryCmd,
+        torrents: &[Torrent],
+        file: Option<&mut W>,
+    ) -> Result<()> {
+        let quiet = self.console.log.level < log::LevelFilter::Info;
+        let (writer, console_out) = self.console.writer_and_out();
+        match file {
+            Some(out) => write_query(&writer, out, qcmd, torrents, false),
+            None => write_query(&writer, console_out, qcmd, torrents, quiet),
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn unseen(&mut self, torrents: Vec<Torrent>) -> Result<Vec<Torrent>> {
+        let hashes: Vec<&str> = torrents
+            .iter()
+            .filter_map(|t| t.hash_string.as_deref())
+            .collect();
+        let unseen: std::collections::HashSet<String> = self
+            .db
+            .unseen_hashes(&hashes)
+            .context("query --new")?
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        Ok(torrents
+            .into_iter()
+            .filter(|t| t.hash_string.as_ref().is_some_and(|h| unseen.contains(h)))
+            .collect())
     }
 
-    pub fn set_location(&mut self, qcmd: &QueryCmd, mv: bool, location: String) -> Result<()> {
-        let torrents: Vec<Torrent> = self.client.torrent_query_sort(None, qcmd)?;
+    #[cfg(not(feature = "sqlite"))]
+    fn unseen(&mut self, _torrents: Vec<Torrent>) -> Result<Vec<Torrent>> {
+        bail!("query --new needs the sqlite feature")
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn mark_seen(&mut self, torrents: &[Torrent]) -> Result<()> {
+        let hashes: Vec<&str> = torrents
+            .iter()
+            .filter_map(|t| t.hash_string.as_deref())
+            .collect();
+        self.db.mark_seen(&hashes).context("query --new")
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    fn mark_seen(&mut self, _torrents: &[Torrent]) -> Result<()> {
+        Ok(())
+    }
+
+    /// The matched torrents in the order that `query` would print them
+    pub fn query_collect(&mut self, qcmd: &QueryCmd) -> Result<Vec<Torrent>> {
+        self.query_sort(qcmd)
+    }
+
+    pub fn set_location(
+        &mut self,
+        qcmd: &QueryCmd,
+        mv: bool,
+        location: String,
+        strict: bool,
+    ) -> Result<()> {
+        let torrents: Vec<Torrent> = self.query_sort(qcmd)?;
         let selected = Self::selectids(&mut self.console, &torrents, None, self.interactive)?;
+        if !mv && !self.is_remote {
+            // transmission would just put the torrent in an error state
+            let mut missing = 0;
+            for &i in &selected {
+                let name = torrents[i].name.as_ref().context("undefined name")?;
+                let path = Path::new(&location).join(name);
+                if !path.exists() {
+                    print_warn!(self.console.log(), "not found: {}", path.display())?;
+                    missing += 1;
+                }
+            }
+            if strict && missing > 0 {
+                bail!("{} torrents not found in {}", missing, location);
+            }
+        }
+        if mv && !self.is_remote {
+            self.check_dst_space(&location, selected.iter().map(|&i| &torrents[i]))?;
+        }
         let ids = selected
             .iter()
             .map(|&i| {
@@ -547,11 +1567,351 @@ impl<T: TorrentCli, O: WriteColor, I: ReadLine> Trctl<T, Console<O, I>> {
         let selected_torrents: Vec<&Torrent> = selected.iter().map(|&i| &torrents[i]).collect();
         self.console
             .torrent_action_ok(selected_torrents, Action::SetLocation { moved: mv })?;
+        self.notify_summary(
+            selected.len(),
+            &format!("Set location of {} torrents", selected.len()),
+        )
+    }
+
+    // Like mv leave dst_free_space_to_leave free. Torrents already in the destination take no
+    // more space.
+    fn check_dst_space<'t>(
+        &mut self,
+        location: &str,
+        torrents: impl Iterator<Item = &'t Torrent>,
+    ) -> Result<()> {
+        let needed: i64 = torrents
+            .filter(|t| {
+                !t.download_dir
+                    .as_deref()
+                    .is_some_and(|d| Path::new(d).starts_with(location))
+            })
+            .filter_map(|t| t.size_when_done.filter(|&s| s > 0))
+            .sum();
+        let free = self.client.free_space(location.to_string())?.size_bytes;
+        let left =
+            free - needed - i64::try_from(self.dst_free_space_to_leave).context("overflow")?;
+        if left < 0 {
+            return Err(anyhow!(NotEnoughSpace).context(format!(
+                "{} more space needed in {}",
+                ByteSize(-left),
+                location
+            )));
+        }
+        Ok(())
+    }
+
+    /// Add the torrent only to print its files. It is removed afterwards unless `keep`, in which
+    /// case it is left stopped.
+    pub fn metadata(&mut self, url: &Url, keep: bool) -> Result<()> {
+        // transmission does not fetch the metadata of a paused magnet and the files cannot be
+        // unwanted before the metadata is in, so it is started and its files unwanted as soon as
+        // the metadata is in
+        let added = self.client.torrent_add(TorrentAddArgs {
+            filename: Some(url.as_str().to_string()),
+            paused: Some(false),
+            ..TorrentAddArgs::default()
+        })?;
+        let (hsh, is_new) = match added {
+            TorrentAdded::TorrentAdded { hash_string, .. } => (hash_string, true),
+            TorrentAdded::TorrentDuplicate { hash_string, .. } => (hash_string, false),
+        };
+        let hsh = hsh.context("undefined hash")?;
+        let res = self.wait_metadata(&hsh, is_new);
+        // leave torrents that were there already alone
+        if is_new {
+            if keep {
+                self.client
+                    .torrent_action(vec![hsh.clone()], TorrentAction::Stop)?;
+            } else {
+                self.client.torrent_remove(vec![hsh.clone()], true)?;
+            }
+        }
+        self.console.print_files(&res?)
+    }
+
+    /// `unwant` leaves all the files undownloaded once the metadata is in
+    fn wait_metadata(&mut self, hsh: &str, unwant: bool) -> Result<Torrent> {
+        let start = std::time::Instant::now();
+        loop {
+            let torrent = self
+                .client
+                .torrent_get(None, Some(vec![client::Id::Hash(hsh.to_string())]))?
+                .into_iter()
+                .next()
+                .context("added torrent not found")?;
+            if torrent.metadata_percent_complete.is_some_and(|p| p >= 1.0) {
+                if unwant {
+                    let count = torrent.files.as_ref().context("undefined files")?.len();
+                    self.client
+                        .set_files_unwanted(hsh.to_string(), (0..count).collect())?;
+                }
+                return Ok(torrent);
+            }
+            if start.elapsed() > METADATA_TIMEOUT {
+                bail!("Timed out waiting for the metadata of {}", hsh);
+            }
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+    }
+
+    pub fn speed(&mut self, down: Option<i64>, up: Option<i64>) -> Result<()> {
+        if down.is_none() && up.is_none() {
+            bail!(NothingToDo("No limits given"));
+        }
+        self.client.session_set_speed(down, up)?;
+        let (down, up) = self.client.session_get_speed()?;
+        for (dir, speed) in [("Download", down), ("Upload", up)] {
+            match speed {
+                None => print_info!(self.console.log(), "{} limit: disabled", dir)?,
+                Some(s) => print_info!(self.console.log(), "{} limit: {} KB/s", dir, s)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Queries the torrents and asks which ones to act on. Returns them with the indexes and
+    /// hashes of the selected ones.
+    fn selected_hashes(
+        &mut self,
+        qcmd: &QueryCmd,
+    ) -> Result<(Vec<Torrent>, Vec<usize>, Vec<String>)> {
+        let torrents: Vec<Torrent> = self.query_sort(qcmd)?;
+        let selected = Self::selectids(&mut self.console, &torrents, None, self.interactive)?;
+        let ids = selected
+            .iter()
+            .map(|&i| {
+                Ok(torrents[i]
+                    .hash_string
+                    .as_ref()
+                    .context("undefined id")?
+                    .clone())
+            })
+            .collect::<Result<Vec<String>>>()?;
+        Ok((torrents, selected, ids))
+    }
+
+    /// Lists the selected torrents as done and sends the summary
+    fn selected_ok(
+        &mut self,
+        torrents: &[Torrent],
+        selected: &[usize],
+        action: Action,
+        summary: &str,
+    ) -> Result<()> {
+        self.console
+            .torrent_action_ok(selected.iter().map(|&i| &torrents[i]), action)?;
+        self.notify_summary(selected.len(), summary)
+    }
+
+    pub fn set_priority(&mut self, qcmd: &QueryCmd, level: PriorityLevel) -> Result<()> {
+        let (torrents, selected, ids) = self.selected_hashes(qcmd)?;
+        self.client.set_priority(ids, level)?;
+        self.selected_ok(
+            &torrents,
+            &selected,
+            Action::Priority(level),
+            &format!("Set {level:?} priority of {} torrents", selected.len()),
+        )
+    }
+
+    /// Make the torrents honor or ignore the session wide speed limits
+    pub fn set_honors_session_limits(&mut self, qcmd: &QueryCmd, honors: bool) -> Result<()> {
+        let (torrents, selected, ids) = self.selected_hashes(qcmd)?;
+        self.client.set_honors_session_limits(ids, honors)?;
+        self.selected_ok(
+            &torrents,
+            &selected,
+            Action::SessionLimits(honors),
+            &format!(
+                "{} session limits for {} torrents",
+                if honors { "Honoring" } else { "Ignoring" },
+                selected.len()
+            ),
+        )
+    }
+
+    /// Assign the torrents to a bandwidth group
+    pub fn set_group(&mut self, qcmd: &QueryCmd, group: String) -> Result<()> {
+        let session = self.client.session_get()?;
+        if session.rpc_version < client::GROUP_RPC_VERSION {
+            bail!(
+                "Bandwidth groups require transmission 4.0 (rpc version {}), {} has {}",
+                client::GROUP_RPC_VERSION,
+                session.version,
+                session.rpc_version
+            );
+        }
+        let (torrents, selected, ids) = self.selected_hashes(qcmd)?;
+        self.client.set_group(ids, group.clone())?;
+        let summary = format!("Set group {group} of {} torrents", selected.len());
+        self.selected_ok(&torrents, &selected, Action::Group(group), &summary)
+    }
+
+    /// Set the download priority of the files of one torrent whose names match the glob
+    pub fn file_priority(
+        &mut self,
+        qcmd: &QueryCmd,
+        glob: &str,
+        level: PriorityLevel,
+    ) -> Result<()> {
+        let re = glob_regex(glob)?;
+        let torrents: Vec<Torrent> = self.query_sort(qcmd)?;
+        let selected = Self::selectids(
+            &mut self.console,
+            &torrents,
+            Some(ConfirmAction::One),
+            self.interactive,
+        )?;
+        let torrent = &torrents[selected[0]];
+        let id = torrent.hash_string.clone().context("undefined id")?;
+        let files: Vec<usize> = torrent
+            .files
+            .as_ref()
+            .context("undefined files")?
+            .iter()
+            .enumerate()
+            .filter(|(_, file)| re.is_match(&file.name))
+            .map(|(i, _)| i)
+            .collect();
+        if files.is_empty() {
+            bail!(
+                "No files of {} match {glob}",
+                torrent.name.as_deref().unwrap_or("no name")
+            );
+        }
+        let count = files.len();
+        self.client.set_file_priority(id, files, level)?;
+        print_info!(
+            self.console.log(),
+            "Set {level:?} priority of {count} files of {}",
+            torrent.name.as_deref().unwrap_or("no name")
+        )?;
         Ok(())
     }
 
     pub fn action(&mut self, ori_qcmd: &QueryCmd, action: TorrentAction) -> Result<()> {
         let mut qcmd = ori_qcmd.clone();
+        Self::restrict_to_action(&mut qcmd, action);
+        let (torrents, selected, ids) = self.selected_hashes(&qcmd)?;
+        self.client.torrent_action(ids, action)?;
+        self.selected_ok(
+            &torrents,
+            &selected,
+            Action::TorrentAction(action),
+            &format!("{}: {} torrents", ActionName(action), selected.len()),
+        )
+    }
+
+    /// Lists the torrents with an error and reannounces or verifies them. Asks which one when
+    /// interactive and no recovery is given.
+    pub fn errors(&mut self, qcmd: &QueryCmd, recover: Option<TorrentAction>) -> Result<()> {
+        let mut qcmd = qcmd.clone();
+        qcmd.error = true;
+        let torrents: Vec<Torrent> = self.query_sort(&qcmd)?;
+        let asking = recover.is_none() && self.interactive;
+        self.print_errors(&torrents, asking)?;
+        let action = match recover {
+            Some(action) => action,
+            None if !self.interactive => return Ok(()),
+            None if self.console.yesno("Reannounce them?")? => TorrentAction::Reannounce,
+            None if self.console.yesno("Verify them?")? => TorrentAction::Verify,
+            None => return Ok(()),
+        };
+        // the ones listed, not queried again
+        let ids = torrents
+            .iter()
+            .map(|t| Ok(t.hash_string.as_ref().context("undefined id")?.clone()))
+            .collect::<Result<Vec<String>>>()?;
+        self.client.torrent_action(ids, action)?;
+        self.console
+            .torrent_action_ok(&torrents, Action::TorrentAction(action))?;
+        self.notify_summary(
+            torrents.len(),
+            &format!("{}: {} torrents", ActionName(action), torrents.len()),
+        )
+    }
+
+    // A listing like the table so quieted the same way, unless asked about
+    fn print_errors(&mut self, torrents: &[Torrent], asking: bool) -> Result<()> {
+        if !asking && self.console.log.level < log::LevelFilter::Info {
+            return Ok(());
+        }
+        let out = self.console.out();
+        for tor in torrents {
+            writeln!(
+                out,
+                "{}: {}",
+                tor.id.unwrap_or(0),
+                tor.name.as_deref().unwrap_or("no name")
+            )?;
+            writeln!(
+                out,
+                "       error: {}",
+                tor.error_string.as_deref().unwrap_or("")
+            )?;
+            if matches!(
+                tor.error,
+                Some(ErrorType::TrackerWarning | ErrorType::TrackerError)
+            ) {
+                for stat in tor.tracker_stats.iter().flatten() {
+                    if stat.has_announced && !stat.last_announce_succeeded {
+                        writeln!(out, "       {}: {}", stat.host, stat.last_announce_result)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the action to every torrent it would change with a single request. Asks once
+    /// instead of for each torrent when interactive.
+    pub fn action_all(&mut self, action: TorrentAction) -> Result<()> {
+        let mut qcmd = QueryCmd::default();
+        Self::restrict_to_action(&mut qcmd, action);
+        let torrents = match self.query_sort(&qcmd) {
+            Err(err) if err.downcast_ref::<NoMatches>().is_some() => Vec::new(),
+            res => res?,
+        };
+        let ids = torrents
+            .iter()
+            .map(|t| Ok(t.hash_string.as_ref().context("undefined id")?.clone()))
+            .collect::<Result<Vec<String>>>()?;
+        let count = ids.len();
+        if count == 0 {
+            print_info!(
+                self.console.log(),
+                "{}: no torrents to change",
+                ActionName(action)
+            )?;
+            return Ok(());
+        }
+        if self.interactive
+            && !self
+                .console
+                .yesno(&format!("{} all {count} torrents", ActionName(action)))?
+        {
+            bail!(NothingToDo("Nothing to do"));
+        }
+        self.client.torrent_action(ids, action)?;
+        print_info!(
+            self.console.log(),
+            "{}: {} torrents",
+            ActionName(action),
+            count
+        )?;
+        self.notify_summary(count, &format!("{}: {count} torrents", ActionName(action)))
+    }
+
+    // Only the torrents the action would change. Statuses given by the user are kept as they
+    // are, as the statuses only add up.
+    fn restrict_to_action(qcmd: &mut QueryCmd, action: TorrentAction) {
+        if qcmd.has_status() {
+            if matches!(action, TorrentAction::Start | TorrentAction::StartNow) {
+                qcmd.finished = Some(false);
+            }
+            return;
+        }
         match action {
             TorrentAction::StartNow => {
                 qcmd.status.extend_from_slice(&[
@@ -575,27 +1935,207 @@ impl<T: TorrentCli, O: WriteColor, I: ReadLine> Trctl<T, Console<O, I>> {
             ]),
             _ => {}
         }
-        let torrents: Vec<Torrent> = self.client.torrent_query_sort(None, &qcmd)?;
-        let selected = Self::selectids(&mut self.console, &torrents, None, self.interactive)?;
+    }
 
-        let ids = selected
-            .iter()
-            .map(|&i| {
-                Ok(torrents[i]
-                    .hash_string
-                    .as_ref()
-                    .context("undefined id")?
-                    .clone())
-            })
-            .collect::<Result<Vec<String>>>()?; // TODO does this short circuit on err or are all the elements collected
-                                                // first??
-        self.client.torrent_action(ids.clone(), action)?;
-        let selected_torrents: Vec<&Torrent> = selected.iter().map(|&i| &torrents[i]).collect();
+    /// Move torrents back to `<dldir>/<hash>` where add puts them
+    pub fn move_back(&mut self, qcmd: &QueryCmd, dldir: Option<&Path>, verify: bool) -> Result<()> {
+        if self.is_remote {
+            bail!("Cannot move back torrents in a remote host");
+        }
+        let dldir = match dldir {
+            Some(d) => d.to_path_buf(),
+            None => self.dldirs.first().context("no dldirs configured")?.clone(),
+        };
+
+        let torrents: Vec<Torrent> = self.query_sort(qcmd)?;
+        let selected = Self::selectids(&mut self.console, &torrents, None, self.interactive)?;
+        let mut ids = Vec::with_capacity(selected.len());
+        let mut pairs = Vec::with_capacity(selected.len());
+        let mut moved = Vec::with_capacity(selected.len());
+        for i in selected {
+            let tor = &torrents[i];
+            let hsh = tor.hash_string.as_ref().context("undefined id")?;
+            let name = tor.name.as_deref().unwrap_or("<unknown>");
+            let location = self.console.base_dir.join(&dldir).join(hsh);
+            if tor.download_dir.as_deref().map(Path::new) == Some(location.as_path()) {
+                print_warn!(
+                    self.console.log(),
+                    "{}: already in {}",
+                    name,
+                    location.display()
+                )?;
+                continue;
+            }
+            pairs.push((vec![hsh.clone()], location.to_string_lossy().to_string()));
+            ids.push(hsh.clone());
+            moved.push(tor);
+        }
+        if ids.is_empty() {
+            bail!(NothingToDo("Nothing to move back"));
+        }
+        self.client.set_locations(pairs, true)?;
         self.console
-            .torrent_action_ok(selected_torrents, Action::TorrentAction(action))?;
+            .torrent_action_ok(moved, Action::SetLocation { moved: true })?;
+        if verify {
+            self.client
+                .torrent_action(ids.clone(), TorrentAction::Verify)?;
+            print_info!(self.console.log(), "Verifying {} torrents", ids.len())?;
+        }
+        self.notify_summary(ids.len(), &format!("Moved back {} torrents", ids.len()))
+    }
+
+    /// Copies the torrent files that transmission keeps to `<out>/<hash>.torrent`. Existing files
+    /// are left as they are.
+    pub fn export(&mut self, qcmd: &QueryCmd, out: &Path) -> Result<()> {
+        if self.is_remote {
+            bail!(
+                "Cannot export from a remote host, the rpc only gives the path of the torrent file"
+            );
+        }
+        std::fs::create_dir_all(out).with_context(|| format!("create {}", out.display()))?;
+        let torrents = self.query_sort(qcmd)?;
+        let mut exported = 0;
+        for tor in &torrents {
+            let hsh = tor.hash_string.as_ref().context("undefined hash")?;
+            let src = tor
+                .torrent_file
+                .as_ref()
+                .context("undefined torrent file")?;
+            let dst = hash_torrent_path(out, hsh);
+            if dst.exists() {
+                print_debug!(self.console.log(), "{} exists", dst.display())?;
+                continue;
+            }
+            let mut tmp = dst.clone().into_os_string();
+            tmp.push(".tmp");
+            std::fs::copy(src, &tmp).with_context(|| format!("copy {src}"))?;
+            std::fs::rename(&tmp, &dst).with_context(|| format!("rename to {}", dst.display()))?;
+            exported += 1;
+        }
+        print_info!(
+            self.console.log(),
+            "Exported {} of {} torrent files to {}",
+            exported,
+            torrents.len(),
+            out.display()
+        )?;
         Ok(())
     }
 
+    /// Delete the data of torrents but keep them in transmission. They are stopped first and
+    /// verified after so that transmission knows the data is gone.
+    pub fn wipe_data(&mut self, qcmd: &QueryCmd) -> Result<()> {
+        if self.is_remote {
+            bail!("Cannot wipe the data of torrents in a remote host");
+        }
+        let (torrents, total) = self.query_sort_total(qcmd)?;
+        self.check_matches_all(qcmd, torrents.len(), total)?;
+        let selected = Self::selectids(&mut self.console, &torrents, None, self.interactive)?;
+        if self.interactive {
+            if !self
+                .console
+                .confirm_count(selected.len(), "wipe the data of")?
+            {
+                bail!(NothingToDo("Count not confirmed"));
+            }
+        } else if !qcmd.i_really_mean_it {
+            bail!("Wiping data needs --i-really-mean-it with --yes");
+        }
+
+        let mut ids = Vec::with_capacity(selected.len());
+        let mut dirs = Vec::with_capacity(selected.len());
+        for &i in &selected {
+            let tor = &torrents[i];
+            let hsh = tor.hash_string.as_ref().context("undefined id")?;
+            let name = tor.name.as_deref().unwrap_or("<unknown>");
+            let dir = Path::new(tor.download_dir.as_deref().context("no dldir")?);
+            // only the hash dirs that add creates are known to hold nothing else
+            if dir.file_name() != Some(std::ffi::OsStr::new(hsh)) || dir.is_symlink() {
+                print_warn!(
+                    self.console.log(),
+                    "{}: {} is not a hash dir, skipping",
+                    name,
+                    dir.display()
+                )?;
+                continue;
+            }
+            ids.push(hsh.clone());
+            dirs.push(dir);
+        }
+        if ids.is_empty() {
+            bail!(NothingToDo("Nothing to wipe"));
+        }
+
+        self.client
+            .torrent_action(ids.clone(), TorrentAction::Stop)?;
+        for dir in dirs {
+            print_info!(self.console.log(), "wipe {}", dir.display())?;
+            if dir.exists() {
+                std::fs::remove_dir_all(dir)
+                    .with_context(|| format!("removing {}", dir.display()))?;
+            }
+            std::fs::create_dir(dir).with_context(|| format!("creating {}", dir.display()))?;
+        }
+        self.client
+            .torrent_action(ids.clone(), TorrentAction::Verify)?;
+        print_info!(self.console.log(), "Verifying {} torrents", ids.len())?;
+        self.notify_summary(
+            ids.len(),
+            &format!("Wiped the data of {} torrents", ids.len()),
+        )
+    }
+
+    /// Move the completed files of torrents to the destination, leaving symlinks in their place.
+    /// Transmission has a single location per torrent so it keeps seeding the moved files
+    /// through the links while the rest download.
+    pub fn mv_completed_files(&mut self, qcmd: &QueryCmd, destination: &Path) -> Result<()> {
+        if self.is_remote {
+            bail!("Cannot move the files of torrents in a remote host");
+        }
+        // the links need an absolute target
+        let destination = destination.canonicalize().with_context(|| {
+            format!(
+                "Destination {} is not an existing directory",
+                destination.display()
+            )
+        })?;
+
+        let torrents: Vec<Torrent> = self.query_sort(qcmd)?;
+        let selected = Self::selectids(&mut self.console, &torrents, None, self.interactive)?;
+        let mut moved = 0;
+        for &i in &selected {
+            let tor = &torrents[i];
+            let dldir = Path::new(tor.download_dir.as_deref().context("undefined dldir")?);
+            for file in tor.files.as_ref().context("undefined files")? {
+                if file.length == 0 || file.bytes_completed != file.length {
+                    continue;
+                }
+                let src = dldir.join(&file.name);
+                // moved already
+                if src.is_symlink() {
+                    continue;
+                }
+                let dst = destination.join(&file.name);
+                if dst.exists() {
+                    bail!("{} exists", dst.display());
+                }
+                print_info!(self.console.log(), "mv {}", file.name)?;
+                move_file(&src, &dst)?;
+                std::os::unix::fs::symlink(&dst, &src)
+                    .with_context(|| format!("linking {}", src.display()))?;
+                moved += 1;
+            }
+        }
+        self.notify_summary(
+            moved,
+            &format!(
+                "Moved {moved} completed files of {} torrents",
+                selected.len()
+            ),
+        )
+    }
+
+    #[allow(clippy::too_many_lines)]
     pub fn mv(
         &mut self,
         qcmd: &QueryCmd,
@@ -603,23 +2143,36 @@ impl<T: TorrentCli, O: WriteColor, I: ReadLine> Trctl<T, Console<O, I>> {
         force: bool,
         verify: Option<bool>,
         config_path: &Path,
+        format: OutputFormat,
     ) -> Result<()> {
         if self.is_remote {
             bail!("Cannot mv files in a remote host");
         }
+        // the move script would create a mistyped destination
+        if !destination.is_dir() {
+            bail!(
+                "Destination {} is not an existing directory",
+                destination.display()
+            );
+        }
 
-        let filtered: Vec<Torrent> = self.client.torrent_query_sort(None, qcmd)?;
+        let filtered: Vec<Torrent> = self.query_sort(qcmd)?;
         let selected = Self::selectids(&mut self.console, &filtered, None, self.interactive)?;
+        let json = format == OutputFormat::Json;
 
         let mut last_error = None;
         let mut errors = 0;
+        let count = selected.len();
+        let mut results = Vec::with_capacity(count);
         for i in selected {
             let tor = &filtered[i];
-            print_info!(
-                self.console.log(),
-                "mv {}",
-                tor.name.as_deref().unwrap_or("missing")
-            )?;
+            if !json {
+                print_info!(
+                    self.console.log(),
+                    "mv {}",
+                    tor.name.as_deref().unwrap_or("missing")
+                )?;
+            }
 
             let mut p = std::process::Command::new("/usr/lib/trctl/move.sh");
             p.env(
@@ -660,13 +2213,40 @@ impl<T: TorrentCli, O: WriteColor, I: ReadLine> Trctl<T, Console<O, I>> {
                     .ok_or_else(|| anyhow!("torrent hash missing"))?,
             );
             p.env("TR_TORRENT_DESTINATION", destination);
+            if json {
+                // keep stdout for the results
+                p.stdout(std::io::stderr());
+            }
             let status = p.status()?;
+            let outcome = match status.code() {
+                _ if status.success() => MoveOutcome::Moved,
+                Some(3) => MoveOutcome::NotEnoughSpace,
+                _ => MoveOutcome::Failed,
+            };
             if !status.success() {
                 last_error = status.code();
                 errors += 1;
                 print_warn!(self.console.log(), "move: {:?}", status)?;
             }
+            results.push(MoveResult {
+                hash: tor.hash_string.clone().unwrap_or_default(),
+                name: tor.name.clone().unwrap_or_default(),
+                destination: destination.to_path_buf(),
+                outcome,
+                code: status.code(),
+            });
+        }
+        if json {
+            #[derive(serde::Serialize)]
+            struct Report {
+                torrents: Vec<MoveResult>,
+            }
+            self.console.print_json(&Report { torrents: results })?;
         }
+        self.notify_summary(
+            count - errors,
+            &format!("Moved {} of {} torrents", count - errors, count),
+        )?;
         if errors > 1 {
             bail!(Multiple(errors))
         }
@@ -680,7 +2260,7 @@ impl<T: TorrentCli, O: WriteColor, I: ReadLine> Trctl<T, Console<O, I>> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::client::MockRequest;
+    use crate::client::{HashColumn, MockRequest};
     use crate::config::Config;
     use crate::console::imps::tests::MockCon;
     use crate::console::DefLog;
@@ -739,45 +2319,1172 @@ mod tests {
             trctl.query(&qcmd).unwrap();
             assert_eq!(
                 trctl.console.log.to_string().unwrap(),
-                "ID     Done     Have     Size       ETA       Up     Down  Ratio  Status     Name\n   \
-                    1   100%     2.4G     2.4G   Unknown        0        0    0.8  Idle       dl//testing.pdf\n\
+                "ID     Done     Have     Size       ETA       Up     Down  Ratio    Age  Status     Name\n   \
+                    1   100%     2.4G     2.4G   Unknown        0        0    0.8     NA  Stalled    dl//testing.pdf\n\
                  Sum:            2.4G                           0        0\n"
                  );
         });
     }
 
-    fn new_valid_mock<F: FnOnce(&mut Trctl<MockRequest, MockCon>, QueryCmd)>(f: F) {
-        new_mock(|trctl| {
-            let mut qcmd = QueryCmd::default();
-            qcmd.strs.push("testing.pdf".to_string());
-            f(trctl, qcmd);
+    // The directory is removed afterwards
+    fn new_mock_trmv<F: FnOnce(&mut Trmv<MockRequest, MockCon>, &Path)>(name: &str, f: F) {
+        let builder = Config::get_mock();
+        let log = builder.mock_log().unwrap();
+        let mut trmv = builder.mock_trmv(log).unwrap();
+        let dir = std::env::temp_dir().join(format!("trctl-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        f(&mut trmv, &dir);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn add_args<'a>(location: &'a TorrentLoc, dldir: &'a PathBuf) -> AddArgs<'a> {
+        AddArgs {
+            location,
+            dldir: Some(dldir),
+            use_existing: None,
+            expect_hash: None,
+            no_overcommit: false,
+            labels: &[],
+            on_duplicate: OnDuplicate::Log,
+            peer_limit: None,
+            priority: None,
+            headers: &[],
+        }
+    }
+
+    // Named a.torrent
+    fn write_torrent(path: &Path) -> TorrentLoc {
+        std::fs::write(path, b"d4:infod6:lengthi3e4:name1:aee").unwrap();
+        TorrentLoc::Path(path.to_path_buf())
+    }
+
+    #[test]
+    fn mock_add_no_dedupe() {
+        new_mock_trmv("no-dedupe", |trmv, dir| {
+            let copydir = dir.join("copy");
+            std::fs::create_dir(&copydir).unwrap();
+            let dldir = dir.join("dl");
+            trmv.copydir = Some(copydir.clone());
+            trmv.copydir_name = CopydirName::Name;
+
+            trmv.disable_dedupe();
+            let loc = write_torrent(&dir.join("1.torrent"));
+            trmv.add(&add_args(&loc, &dldir)).unwrap();
+            assert_eq!(std::fs::read_dir(&copydir).unwrap().count(), 0);
+
+            trmv.dedupe = true;
+            let loc = write_torrent(&dir.join("2.torrent"));
+            trmv.add(&add_args(&loc, &dldir)).unwrap();
+            assert!(copydir.join("a.torrent").is_file());
         });
     }
 
     #[test]
-    #[should_panic(expected = "unexpected end of file")]
-    fn mock_erase_no_selection() {
-        new_valid_mock(|trctl, qcmd| {
-            log::set_max_level(log::LevelFilter::Info);
-            trctl.erase(qcmd, false).expect("ohno");
-            //assert_eq!(trctl.console.log.io.to_string().expect("heww"), "hello");
+    fn mock_add_projected() {
+        new_mock_trmv("projected", |trmv, dir| {
+            let dldir = dir.join("dl");
+            // over the quota so added paused
+            trmv.quota = 1;
+            let loc = write_torrent(&dir.join("1.torrent"));
+            trmv.add(&add_args(&loc, &dldir)).unwrap();
+            assert_eq!(trmv.projected.len(), 1);
+            assert_eq!(trmv.projected[0].size, 3);
+            assert!(trmv.projected[0].paused);
+            let space = trmv.dir_space(&dldir).unwrap();
+            assert_eq!(space.total_size, 3);
+            assert_eq!(space.safe, space.free);
         });
     }
 
     #[test]
-    fn mock_erase() {
-        new_valid_mock(|trctl, qcmd| {
-            trctl.console.input.input = "y\n".to_string();
-            log::set_max_level(log::LevelFilter::Info);
-            trctl.erase(qcmd, false).unwrap();
-            assert_eq!(trctl.console.log.to_string().unwrap(),
-            "ID     Done     Have     Size       ETA       Up     Down  Ratio  Status     Name\n   \
-                1   100%     2.4G     2.4G   Unknown        0        0    0.8  Idle       dl//testing.pdf\n\
-             Sum:            2.4G                           0        0\n\
-             Select [y/N]: -- erase: testing.pdf\n"
+    fn mock_add_quota() {
+        new_mock_trmv("quota", |trmv, dir| {
+            let dldir = dir.join("dl");
+            // only the quota pauses
+            trmv.safe_space = 0;
+            trmv.quota = 1;
+            trmv.strict_quota = true;
+            let loc = write_torrent(&dir.join("1.torrent"));
+            let err = trmv.add(&add_args(&loc, &dldir)).unwrap_err();
+            assert!(err.downcast_ref::<NotEnoughSpace>().is_some());
+            assert!(trmv.projected.is_empty());
+            assert!(dir.join("1.torrent").exists());
+
+            trmv.strict_quota = false;
+            trmv.add(&add_args(&loc, &dldir)).unwrap();
+            assert!(trmv.projected[0].paused);
+            assert!(trmv
+                .view
+                .log
+                .to_string()
+                .unwrap()
+                .contains("would exceed the quota of 1, adding paused"));
+
+            // added before so not checked
+            let copydir = dir.join("copy");
+            std::fs::create_dir(&copydir).unwrap();
+            std::fs::write(
+                copydir.join("55ee333990e491f0e44b57b7f2f22fca5c865e81.torrent"),
+                b"",
+            )
+            .unwrap();
+            trmv.copydir = Some(copydir);
+            trmv.strict_quota = true;
+            trmv.view.input.input = "y\n".to_string();
+            let loc = write_torrent(&dir.join("2.torrent"));
+            trmv.add(&add_args(&loc, &dldir)).unwrap();
+            assert!(!trmv.projected[1].paused);
+        });
+    }
+
+    #[test]
+    fn mock_add_many() {
+        new_mock_trmv("add-many", |trmv, dir| {
+            let dldir = dir.join("dl");
+            let first = write_torrent(&dir.join("1.torrent"));
+            std::fs::write(dir.join("2.torrent"), b"d4:infod6:lengthi5e4:name1:bee").unwrap();
+            let second = TorrentLoc::Path(dir.join("2.torrent"));
+            let missing = TorrentLoc::Path(dir.join("3.torrent"));
+            let args = [
+                add_args(&first, &dldir),
+                add_args(&second, &dldir),
+                add_args(&missing, &dldir),
+            ];
+            let mut helpers = vec![Config::get_mock().new_client().unwrap()];
+            let results = trmv.add_many(&args, &mut helpers);
+            assert_eq!(results.len(), 3);
+            assert!(results[0].is_ok() && results[1].is_ok());
+            assert!(results[2].is_err());
+            let sizes: Vec<i64> = trmv.projected.iter().map(|p| p.size).collect();
+            assert_eq!(sizes, [3, 5]);
+            assert!(!dir.join("1.torrent").exists() && !dir.join("2.torrent").exists());
+        });
+    }
+
+    #[test]
+    fn mock_add_hash_mismatch() {
+        new_mock_trmv("hash-mismatch", |trmv, dir| {
+            let dldir = dir.join("dl");
+            trmv.disable_dedupe();
+            let path = dir.join("1.torrent");
+            let loc = write_torrent(&path);
+            let expected = "0".repeat(40);
+            let err = trmv
+                .add(&AddArgs {
+                    expect_hash: Some(&expected),
+                    ..add_args(&loc, &dldir)
+                })
+                .unwrap_err();
+            let mismatch = err.downcast_ref::<HashMismatch>().unwrap();
+            assert_eq!(mismatch.expected, expected);
+            assert!(path.exists());
+        });
+    }
+
+    #[test]
+    fn mock_add_duplicate() {
+        new_mock_trmv("duplicate", |trmv, dir| {
+            let dldir = dir.join("dl");
+            trmv.disable_dedupe();
+            trmv.client.imp.duplicate = true;
+            let path = dir.join("1.torrent");
+            let loc = write_torrent(&path);
+            trmv.add(&AddArgs {
+                on_duplicate: OnDuplicate::Print,
+                ..add_args(&loc, &dldir)
+            })
+            .unwrap();
+            assert!(trmv.view.log.to_string().unwrap().lines().any(|l| l == "6"));
+            assert!(!path.exists());
+
+            let loc = write_torrent(&path);
+            let err = trmv
+                .add(&AddArgs {
+                    on_duplicate: OnDuplicate::Error,
+                    ..add_args(&loc, &dldir)
+                })
+                .unwrap_err();
+            assert!(err.to_string().contains("already exists with id 6"));
+            assert!(path.exists());
+        });
+    }
+
+    fn new_valid_mock<F: FnOnce(&mut Trctl<MockRequest, MockCon>, QueryCmd)>(f: F) {
+        new_mock(|trctl| {
+            let mut qcmd = QueryCmd::default();
+            qcmd.strs.push("testing.pdf".to_string());
+            f(trctl, qcmd);
+        });
+    }
+
+    #[test]
+    fn mock_query_completion_format() {
+        new_mock(|trctl| {
+            trctl.client.imp.mock_data[0].name = Some("testing (1): a.pdf".to_string());
+            let qcmd = QueryCmd {
+                completion_format: true,
+                strs: vec!["a.pdf".to_string()],
+                ..QueryCmd::default()
+            };
+            trctl.query(&qcmd).unwrap();
+            assert_eq!(
+                trctl.console.log.to_string().unwrap(),
+                "testing\\ \\(1\\)\\:\\ a.pdf\n"
+            );
+        });
+    }
+
+    #[test]
+    fn mock_query_quiet() {
+        new_mock(|trctl| {
+            trctl.console.log.level = log::LevelFilter::Warn;
+            trctl.query(&QueryCmd::default()).unwrap();
+            assert_eq!(trctl.console.log.to_string().unwrap(), "");
+        });
+    }
+
+    #[test]
+    fn mock_query_output() {
+        new_mock(|trctl| {
+            let path = std::env::temp_dir().join(format!("trctl-output-{}", std::process::id()));
+            let qcmd = QueryCmd {
+                names_only: true,
+                output: Some(path.clone()),
+                strs: vec!["testing".to_string()],
+                ..QueryCmd::default()
+            };
+            trctl.query(&qcmd).unwrap();
+            let written = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(written, "testing.pdf\ntesting2.pdf\ntesting3.pdf\n");
+            assert_eq!(trctl.console.log.to_string().unwrap(), "");
+
+            // a failed query leaves the earlier output as it was
+            let failing = QueryCmd {
+                output: Some(path.clone()),
+                strs: vec!["not found".to_string()],
+                ..QueryCmd::default()
+            };
+            assert!(trctl.query(&failing).is_err());
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), written);
+            assert!(!output_tmp_path(&path).unwrap().exists());
+            std::fs::remove_file(&path).unwrap();
+
+            let qcmd = QueryCmd {
+                output: Some(path.join("missing")),
+                ..QueryCmd::default()
+            };
+            assert!(trctl.query(&qcmd).is_err());
+        });
+    }
+
+    #[test]
+    fn mock_query_collect() {
+        new_mock(|trctl| {
+            let qcmd = QueryCmd {
+                strs: vec!["testing".to_string()],
+                reverse: true,
+                ..QueryCmd::default()
+            };
+            let ids: Vec<Option<i64>> = trctl
+                .query_collect(&qcmd)
+                .unwrap()
+                .iter()
+                .map(|t| t.id)
+                .collect();
+            assert_eq!(ids, [Some(3), Some(2), Some(1)]);
+            assert_eq!(trctl.console.log.to_string().unwrap(), "");
+        });
+    }
+
+    #[test]
+    fn mock_query_names_only() {
+        new_mock(|trctl| {
+            let mut qcmd = QueryCmd {
+                names_only: true,
+                ..QueryCmd::default()
+            };
+            qcmd.strs.push("testing".to_string());
+            trctl.query(&qcmd).unwrap();
+            qcmd.strs = vec!["not found".to_string()];
+            trctl.query(&qcmd).unwrap();
+            assert_eq!(
+                trctl.console.log.to_string().unwrap(),
+                "testing.pdf\ntesting2.pdf\ntesting3.pdf\n"
+            );
+        });
+    }
+
+    #[test]
+    fn mock_query_show_hash() {
+        new_valid_mock(|trctl, qcmd| {
+            trctl.console.show_hash = Some(HashColumn::Short);
+            trctl.query(&qcmd).unwrap();
+            let out = trctl.console.log.to_string().unwrap();
+            let mut lines = out.lines();
+            assert!(lines.next().unwrap().starts_with("Hash      ID "));
+            assert!(lines.next().unwrap().starts_with("abed48ad     1 "));
+            assert!(lines.next().unwrap().starts_with("          Sum: "));
+        });
+    }
+
+    #[test]
+    fn mock_query_swarm() {
+        new_valid_mock(|trctl, mut qcmd| {
+            qcmd.swarm = true;
+            trctl.query(&qcmd).unwrap();
+            let out = trctl.console.log.to_string().unwrap();
+            let mut lines = out.lines();
+            assert!(lines.next().unwrap().starts_with("Seeds  Peers  ID "));
+            assert!(lines.next().unwrap().starts_with("   NA     NA     1 "));
+            assert!(lines.next().unwrap().starts_with("              Sum: "));
+        });
+    }
+
+    #[test]
+    fn mock_query_seed_time() {
+        new_valid_mock(|trctl, mut qcmd| {
+            qcmd.sort = Some(Sort::SeedTime);
+            trctl.query(&qcmd).unwrap();
+            let out = trctl.console.log.to_string().unwrap();
+            assert!(out.contains("  Age   Seed  Status     Name\n"));
+            assert!(out.contains("  NA     0s  Stalled    dl//testing.pdf\n"));
+        });
+    }
+
+    #[test]
+    fn mock_query_template() {
+        new_valid_mock(|trctl, mut qcmd| {
+            qcmd.template = Some(template::Template::parse("{id}: {name} in {dir}").unwrap());
+            trctl.query(&qcmd).unwrap();
+            assert_eq!(
+                trctl.console.log.to_string().unwrap(),
+                "1: testing.pdf in dl/\n"
+            );
+        });
+    }
+
+    #[test]
+    fn mock_query_no_header() {
+        new_valid_mock(|trctl, mut qcmd| {
+            qcmd.no_header = true;
+            trctl.query(&qcmd).unwrap();
+            assert!(trctl
+                .console
+                .log
+                .to_string()
+                .unwrap()
+                .starts_with("   1   100%"));
+        });
+    }
+
+    #[test]
+    fn mock_query_porcelain() {
+        new_valid_mock(|trctl, mut qcmd| {
+            qcmd.porcelain = true;
+            trctl.client.imp.mock_data[0].name = Some("testing.pdf\tx".to_string());
+            trctl.query(&qcmd).unwrap();
+            let out = trctl.console.log.to_string().unwrap();
+            let fields: Vec<&str> = out.trim_end_matches('\n').split('\t').collect();
+            assert_eq!(fields.len(), 13);
+            assert_eq!(fields[0], "1");
+            assert_eq!(fields[1], "abed48adeb5e396f54a7089cbe6c1f2bc1b0dbc8");
+            assert_eq!(fields[3], "1");
+            assert_eq!(fields[11], "testing.pdf\\tx");
+        });
+    }
+
+    #[test]
+    fn mock_query_print0() {
+        new_mock(|trctl| {
+            let qcmd = QueryCmd {
+                names_only: true,
+                print0: true,
+                strs: vec!["testing".to_string()],
+                ..QueryCmd::default()
+            };
+            trctl.query(&qcmd).unwrap();
+            assert_eq!(
+                trctl.console.log.to_string().unwrap(),
+                "testing.pdf\0testing2.pdf\0testing3.pdf\0"
+            );
+        });
+    }
+
+    #[test]
+    fn dedupe_by_name() {
+        let torrents: Vec<Torrent> = [(1, "a", 10), (2, "b", 10), (3, "a", 30), (4, "a", 20)]
+            .into_iter()
+            .map(|(id, name, added)| {
+                let mut t = client::test_torrent(id, name);
+                t.added_date = Some(client::test_date(added));
+                t
+            })
+            .collect();
+        let deduped = super::dedupe_by_name(torrents);
+        let shown: Vec<(Option<i64>, Option<&str>)> =
+            deduped.iter().map(|t| (t.id, t.name.as_deref())).collect();
+        assert_eq!(shown, [(Some(3), Some("a (x3)")), (Some(2), Some("b"))]);
+    }
+
+    #[test]
+    fn mock_query_group_by() {
+        new_valid_mock(|trctl, mut qcmd| {
+            qcmd.group_by = Some(GroupBy::Dldir);
+            trctl.query(&qcmd).unwrap();
+            assert_eq!(
+                trctl.console.log.to_string().unwrap(),
+                "Count    Have      Up    Down  Dir\n    \
+                     1    2.4G       0       0  dl/\n    \
+                     1    2.4G       0       0  Sum\n"
+            );
+        });
+    }
+
+    #[test]
+    fn mock_metadata() {
+        new_mock(|trctl| {
+            let file = |name: &str| transmission_rpc::types::File {
+                length: 10,
+                bytes_completed: 0,
+                name: name.into(),
+                begin_piece: None,
+                end_piece: None,
+            };
+            trctl.client.imp.mock_data[0].files = Some(vec![file("a.mkv"), file("a.nfo")]);
+            let url =
+                Url::parse("magnet:?xt=urn:btih:03a4f88adee883a3a135f10042442894af4167f7").unwrap();
+            trctl.metadata(&url, true).unwrap();
+            let out = trctl.console.log.to_string().unwrap();
+            assert!(out.starts_with("testing.pdf\n"));
+            assert!(out.contains("  a.nfo\n"));
+            assert_eq!(
+                trctl.client.imp.files_unwanted,
+                vec![(
+                    "03a4f88adee883a3a135f10042442894af4167f7".to_string(),
+                    vec![0, 1]
+                )]
+            );
+
+            // a torrent added before is left alone
+            trctl.client.imp.files_unwanted.clear();
+            trctl.client.imp.duplicate = true;
+            trctl.metadata(&url, false).unwrap();
+            assert!(trctl.client.imp.files_unwanted.is_empty());
+        });
+    }
+
+    #[test]
+    fn tracker_dldir() {
+        let map = BTreeMap::from([
+            ("distro.org".to_string(), PathBuf::from("linux")),
+            ("media".to_string(), PathBuf::from("media")),
+        ]);
+        let trackers = ["udp://tracker.distro.org:6969/announce".to_string()];
+        assert_eq!(
+            pick_tracker_dldir(&map, &trackers).map(|(_, dir)| dir),
+            Some(&PathBuf::from("linux"))
+        );
+        assert_eq!(pick_tracker_dldir(&map, &[]), None);
+        assert_eq!(
+            pick_tracker_dldir(&map, &["http://other/announce".to_string()]),
+            None
+        );
+    }
+
+    #[test]
+    fn http_headers() {
+        let map = BTreeMap::from([(
+            "private.org".to_string(),
+            vec!["Cookie: uid=1; pass=x".to_string()],
+        )]);
+        let extra = ["X-Api-Key: k".to_string()];
+        let url = Url::parse("https://private.org/dl/1.torrent").unwrap();
+        let headers = pick_http_headers(&map, &url, &extra);
+        assert_eq!(headers, ["Cookie: uid=1; pass=x", "X-Api-Key: k"]);
+        let url = Url::parse("https://other.org/dl/1.torrent").unwrap();
+        assert_eq!(pick_http_headers(&map, &url, &[]), Vec::<&str>::new());
+
+        let (name, value) = parse_header(headers[0]).unwrap();
+        assert_eq!(name.as_str(), "cookie");
+        assert_eq!(value.to_str().unwrap(), "uid=1; pass=x");
+        assert!(value.is_sensitive());
+        assert!(!format!("{value:?}").contains("pass"));
+        assert!(parse_header("Cookie uid=1").is_err());
+    }
+
+    #[test]
+    fn magnets() {
+        let info = |s: &str| {
+            let url = Url::parse(s).unwrap();
+            magnet_info(&url, &MagnetURI::from_str(s).unwrap()).unwrap()
+        };
+        let hsh = "abed48adeb5e396f54a7089cbe6c1f2bc1b0dbc8";
+        assert_eq!(
+            info("magnet:?xt=urn:btih:ABED48ADEB5E396F54A7089CBE6C1F2BC1B0DBC8"),
+            MagnetInfo {
+                hsh: hsh.to_string(),
+                name: None,
+                length: None,
+                trackers: vec![],
+            }
+        );
+        let m = info(
+            "magnet:?xt=urn:btih:VPWURLPLLY4W6VFHBCOL43A7FPA3BW6I&dn=Some%20Name+%C3%A4%2B1\
+             &xl=1234&tr=http%3A%2F%2Ftracker.org%2Fannounce",
+        );
+        assert_eq!(m.hsh, hsh);
+        assert_eq!(m.name.as_deref(), Some("Some Name ä+1"));
+        assert_eq!(m.length, Some(1234));
+        assert_eq!(m.trackers, ["http://tracker.org/announce"]);
+        assert_eq!(
+            info(&format!("magnet:?xt=urn:btih:{hsh}&xl=0")).length,
+            None
+        );
+        // the length of a hex hash but not hex
+        let url = "magnet:?xt=urn:btih:zbed48adeb5e396f54a7089cbe6c1f2bc1b0dbc8";
+        let magnet = MagnetURI::from_str(url).unwrap();
+        assert!(magnet_info(&Url::parse(url).unwrap(), &magnet).is_err());
+    }
+
+    #[test]
+    fn fetch_torrent_errors() {
+        let url = Url::parse("https://t.org:8080/dl/1.torrent?passkey=s3cret#x").unwrap();
+        assert_eq!(redact_url(&url), "https://t.org:8080/dl/1.torrent");
+
+        new_mock_trmv("fetch", |trmv, _| {
+            let url = Url::parse("ftp://t.org/1.torrent").unwrap();
+            assert!(trmv.fetch_torrent(&url, &[]).is_err());
+            let url = Url::parse("http://127.0.0.1:1/1.torrent").unwrap();
+            let err = trmv
+                .fetch_torrent(&url, &["Cookie uid=1".to_string()])
+                .unwrap_err();
+            assert!(err.to_string().contains("not of the form"));
+
+            // nothing listens on port 1
+            let url = Url::parse("http://127.0.0.1:1/dl?passkey=s3cret").unwrap();
+            let err = trmv.fetch_torrent(&url, &[]).unwrap_err();
+            let msg = format!("{err:#}");
+            assert!(msg.starts_with("fetch torrent from http://127.0.0.1:1/dl: "));
+            assert!(!msg.contains("s3cret"));
+        });
+    }
+
+    #[test]
+    fn glob() {
+        let re = glob_regex("*.mkv").unwrap();
+        assert!(re.is_match("show/ep1.mkv"));
+        assert!(!re.is_match("show/ep1.mkv.part"));
+        let re = glob_regex("ep?.(1)").unwrap();
+        assert!(re.is_match("ep2.(1)"));
+        assert!(!re.is_match("ep12.(1)"));
+    }
+
+    #[test]
+    fn mock_file_priority() {
+        new_valid_mock(|trctl, qcmd| {
+            trctl.interactive = false;
+            let file = |name: &str| transmission_rpc::types::File {
+                length: 10,
+                bytes_completed: 0,
+                name: name.into(),
+                begin_piece: None,
+                end_piece: None,
+            };
+            trctl.client.imp.mock_data[0].files = Some(vec![
+                file("testing/a.mkv"),
+                file("testing/a.nfo"),
+                file("testing/b.mkv"),
+            ]);
+            // a single torrent is still confirmed
+            trctl.console.input.input = "y\ny\n".to_string();
+            assert!(trctl
+                .file_priority(&qcmd, "*.srt", PriorityLevel::Low)
+                .is_err());
+            trctl
+                .file_priority(&qcmd, "*.mkv", PriorityLevel::Low)
+                .unwrap();
+            assert_eq!(
+                trctl.client.imp.file_priorities,
+                vec![(
+                    "abed48adeb5e396f54a7089cbe6c1f2bc1b0dbc8".to_string(),
+                    vec![0, 2],
+                    PriorityLevel::Low
+                )]
+            );
+        });
+    }
+
+    #[test]
+    fn mock_session_limits() {
+        new_valid_mock(|trctl, qcmd| {
+            trctl.interactive = false;
+            trctl.set_honors_session_limits(&qcmd, false).unwrap();
+            assert_eq!(trctl.client.imp.session_limits.len(), 1);
+            assert!(!trctl.client.imp.session_limits[0].1);
+            assert!(trctl
+                .console
+                .log
+                .to_string()
+                .unwrap()
+                .ends_with("-- Session limits ignored by:\n-- 1: testing.pdf\n"));
+        });
+    }
+
+    #[test]
+    fn mock_priority() {
+        new_valid_mock(|trctl, qcmd| {
+            trctl.interactive = false;
+            trctl.set_priority(&qcmd, PriorityLevel::High).unwrap();
+            assert_eq!(trctl.client.imp.priorities.len(), 1);
+            assert_eq!(trctl.client.imp.priorities[0].1, PriorityLevel::High);
+            assert!(trctl
+                .console
+                .log
+                .to_string()
+                .unwrap()
+                .ends_with("-- Priority set to high:\n-- 1: testing.pdf\n"));
+        });
+    }
+
+    #[test]
+    fn mock_space_json() {
+        new_mock(|trctl| {
+            let dirs = [(
+                PathBuf::from("/dl"),
+                DirSpace {
+                    free: 3,
+                    safe: -1,
+                    total_size: 4,
+                },
+            )];
+            trctl
+                .console
+                .print_space(&dirs, 10, client::OutputFormat::Json)
+                .unwrap();
+            assert_eq!(
+                trctl.console.log.to_string().unwrap(),
+                "{\"quota\":10,\"dirs\":[{\"dir\":\"/dl\",\"free\":3,\"safe\":-1,\"total_size\":4}]}\n"
+            );
+        });
+    }
+
+    #[test]
+    fn mock_move_back() {
+        new_valid_mock(|trctl, qcmd| {
+            trctl.interactive = false;
+            let err = trctl.move_back(&qcmd, None, false).unwrap_err();
+            assert!(err.downcast_ref::<NothingToDo>().is_some());
+            assert!(trctl.console.log.to_string().unwrap().ends_with(
+                "-w testing.pdf: already in \
+                 /var/cache/torrents/dl/abed48adeb5e396f54a7089cbe6c1f2bc1b0dbc8\n"
+            ));
+
+            // nothing is reported moved if the request fails
+            trctl.console.log = console::imps::tests::MockView::default();
+            trctl.client.imp.fail_set_location = true;
+            assert!(trctl
+                .move_back(&qcmd, Some(Path::new("/other")), true)
+                .is_err());
+            assert!(!trctl
+                .console
+                .log
+                .to_string()
+                .unwrap()
+                .contains("Torrent moved"));
+
+            trctl.client.imp.fail_set_location = false;
+            trctl
+                .move_back(&qcmd, Some(Path::new("/other")), true)
+                .unwrap();
+            assert!(trctl
+                .console
+                .log
+                .to_string()
+                .unwrap()
+                .ends_with("-- Torrent moved\n-- 1: testing.pdf\n-- Verifying 1 torrents\n"));
+            assert_eq!(
+                trctl.client.imp.locations,
+                [(
+                    vec!["abed48adeb5e396f54a7089cbe6c1f2bc1b0dbc8".to_string()],
+                    "/other/abed48adeb5e396f54a7089cbe6c1f2bc1b0dbc8".to_string()
+                )]
+            );
+
+            trctl.is_remote = true;
+            assert!(trctl.move_back(&qcmd, None, false).is_err());
+        });
+    }
+
+    #[test]
+    fn mock_set_location_space() {
+        new_valid_mock(|trctl, qcmd| {
+            trctl.interactive = false;
+            trctl
+                .set_location(&qcmd, true, "/other".to_string(), false)
+                .unwrap();
+            trctl.dst_free_space_to_leave = 49 * 1024 * 1024 * 1024;
+            let err = trctl
+                .set_location(&qcmd, true, "/other".to_string(), false)
+                .unwrap_err();
+            assert!(err.downcast_ref::<NotEnoughSpace>().is_some());
+            // already there
+            trctl
+                .set_location(&qcmd, true, "/var/cache/torrents".to_string(), false)
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn restrict_to_action() {
+        let mut qcmd = QueryCmd::default();
+        Trctl::<MockRequest, MockCon>::restrict_to_action(&mut qcmd, TorrentAction::StartNow);
+        assert!(qcmd.status.contains(&client::MyTorrentStatus::Stopped));
+
+        let mut qcmd = QueryCmd {
+            queued: true,
+            ..QueryCmd::default()
+        };
+        Trctl::<MockRequest, MockCon>::restrict_to_action(&mut qcmd, TorrentAction::StartNow);
+        assert!(qcmd.status.is_empty());
+        assert_eq!(qcmd.finished, Some(false));
+    }
+
+    #[test]
+    fn mock_errors() {
+        new_mock(|trctl| {
+            trctl.interactive = false;
+            trctl.client.imp.mock_data[1].error = Some(ErrorType::LocalError);
+            trctl.errors(&QueryCmd::default(), None).unwrap();
+            assert_eq!(
+                trctl.console.log.to_string().unwrap(),
+                "2: testing2.pdf\n       error: error!!!\n"
+            );
+
+            // answering no to reannounce, yes to verify
+            trctl.interactive = true;
+            trctl.console.log = console::imps::tests::MockView::default();
+            trctl.console.input.input = "n\ny\n".to_string();
+            trctl.errors(&QueryCmd::default(), None).unwrap();
+            assert!(trctl
+                .console
+                .log
+                .to_string()
+                .unwrap()
+                .ends_with("Verify them? [y/N]: -- Verifying:\n-- 2: testing2.pdf\n"));
+            trctl.interactive = false;
+
+            trctl.console.log = console::imps::tests::MockView::default();
+            trctl.console.log.level = log::LevelFilter::Warn;
+            trctl
+                .errors(&QueryCmd::default(), Some(TorrentAction::Verify))
+                .unwrap();
+            assert_eq!(trctl.console.log.to_string().unwrap(), "");
+
+            trctl.client.imp.mock_data[1].error = Some(ErrorType::Ok);
+            let err = trctl.errors(&QueryCmd::default(), None).unwrap_err();
+            assert!(err.downcast_ref::<NoMatches>().is_some());
+        });
+    }
+
+    #[test]
+    fn mock_oldest_newest() {
+        new_mock(|trctl| {
+            for (tor, added) in trctl.client.imp.mock_data.iter_mut().zip([30, 10, 20]) {
+                tor.added_date = Some(client::test_date(added));
+            }
+            let ids = |torrents: Vec<Torrent>| -> Vec<i64> {
+                torrents.iter().map(|t| t.id.unwrap()).collect()
+            };
+            let mut qcmd = QueryCmd {
+                oldest: Some(2),
+                ..QueryCmd::default()
+            };
+            assert_eq!(ids(trctl.query_sort(&qcmd).unwrap()), [2, 3]);
+            qcmd.oldest = None;
+            qcmd.newest = Some(1);
+            assert_eq!(ids(trctl.query_sort(&qcmd).unwrap()), [1]);
+            trctl.client.imp.mock_data[0].added_date = None;
+            assert_eq!(ids(trctl.query_sort(&qcmd).unwrap()), [3]);
+        });
+    }
+
+    #[test]
+    fn mock_action_all() {
+        new_mock(|trctl| {
+            trctl.console.input.input = "n\n".to_string();
+            let err = trctl.action_all(TorrentAction::Stop).unwrap_err();
+            assert!(err.downcast_ref::<NothingToDo>().is_some());
+
+            trctl.interactive = false;
+            trctl.action_all(TorrentAction::Stop).unwrap();
+            assert!(trctl
+                .console
+                .log
+                .to_string()
+                .unwrap()
+                .contains("Stop: 3 torrents"));
+        });
+    }
+
+    #[test]
+    fn mock_mv_missing_destination() {
+        new_valid_mock(|trctl, qcmd| {
+            let err = trctl
+                .mv(
+                    &qcmd,
+                    Path::new("/nonexistent/trctl/dst"),
+                    false,
+                    None,
+                    Path::new("config.toml"),
+                    OutputFormat::Text,
+                )
+                .unwrap_err();
+            assert!(err.to_string().contains("not an existing directory"));
+            assert_eq!(trctl.console.log.to_string().unwrap(), "");
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn mock_query_new() {
+        new_valid_mock(|trctl, mut qcmd| {
+            qcmd.new = true;
+            qcmd.names_only = true;
+            assert!(trctl.query(&qcmd).is_err());
+
+            let path =
+                std::env::temp_dir().join(format!("trctl-new-{}.sqlite3", std::process::id()));
+            trctl.db = DBSqlite::new(Some(path.clone()));
+            trctl.query(&qcmd).unwrap();
+            trctl.query(&qcmd).unwrap();
+            assert_eq!(trctl.console.log.to_string().unwrap(), "testing.pdf\n");
+            std::fs::remove_file(path).unwrap();
+        });
+    }
+
+    #[test]
+    fn mock_export() {
+        new_valid_mock(|trctl, qcmd| {
+            let dir = std::env::temp_dir().join(format!("trctl-export-{}", std::process::id()));
+            let src = dir.join("src.torrent");
+            let out = dir.join("out");
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(&src, "d4:infode").unwrap();
+            trctl.client.imp.mock_data[0].torrent_file = Some(src.to_string_lossy().to_string());
+            trctl.export(&qcmd, &out).unwrap();
+            trctl.export(&qcmd, &out).unwrap();
+            let dst = out.join("abed48adeb5e396f54a7089cbe6c1f2bc1b0dbc8.torrent");
+            assert_eq!(std::fs::read_to_string(dst).unwrap(), "d4:infode");
+            let log = trctl.console.log.to_string().unwrap();
+            assert!(log.contains("Exported 1 of 1 torrent files"));
+            assert!(log.contains("Exported 0 of 1 torrent files"));
+
+            trctl.is_remote = true;
+            assert!(trctl.export(&qcmd, &out).is_err());
+            std::fs::remove_dir_all(dir).unwrap();
+        });
+    }
+
+    #[test]
+    fn mock_retry() {
+        new_valid_mock(|trctl, qcmd| {
+            trctl.client.imp.fail_connection = 1;
+            trctl.console.input.input = "y\n".to_string();
+            trctl.query(&qcmd).unwrap();
+            assert!(trctl
+                .console
+                .log
+                .to_string()
+                .unwrap()
+                .contains("rpc call: connection refused"));
+
+            trctl.client.imp.fail_connection = 1;
+            trctl.console.input.input = "n\n".to_string();
+            trctl.console.input.input_pos = 0;
+            assert!(trctl.query(&qcmd).is_err());
+
+            trctl.client.imp.fail_connection = 1;
+            trctl.interactive = false;
+            assert!(trctl.query(&qcmd).is_err());
+        });
+    }
+
+    #[test]
+    fn mock_wipe_data() {
+        new_valid_mock(|trctl, mut qcmd| {
+            let err = trctl.wipe_data(&qcmd).unwrap_err();
+            assert!(format!("{err:#}").contains("end of file"));
+
+            trctl.interactive = false;
+            assert!(trctl.wipe_data(&qcmd).is_err());
+
+            let dir = std::env::temp_dir().join(format!("trctl-wipe-{}", std::process::id()));
+            let hsh = trctl.client.imp.mock_data[0].hash_string.clone().unwrap();
+            let hash_dir = dir.join(hsh);
+            std::fs::create_dir_all(&hash_dir).unwrap();
+            std::fs::write(hash_dir.join("testing.pdf"), "data").unwrap();
+            trctl.client.imp.mock_data[0].download_dir = Some(hash_dir.to_string_lossy().into());
+            qcmd.i_really_mean_it = true;
+            trctl.wipe_data(&qcmd).unwrap();
+            assert!(hash_dir.is_dir());
+            assert!(!hash_dir.join("testing.pdf").exists());
+            std::fs::remove_dir_all(dir).unwrap();
+
+            trctl.client.imp.mock_data[0].download_dir = Some("/var/cache/other".into());
+            let err = trctl.wipe_data(&qcmd).unwrap_err();
+            assert!(err.downcast_ref::<NothingToDo>().is_some());
+
+            trctl.is_remote = true;
+            assert!(trctl.wipe_data(&qcmd).is_err());
+        });
+    }
+
+    #[test]
+    fn mock_group_old_daemon() {
+        new_valid_mock(|trctl, qcmd| {
+            let err = trctl.set_group(&qcmd, "slow".to_string()).unwrap_err();
+            assert!(err.to_string().contains("require transmission 4.0"));
+            assert!(trctl.client.imp.groups.is_empty());
+        });
+    }
+
+    #[test]
+    fn mock_group() {
+        new_valid_mock(|trctl, qcmd| {
+            trctl.interactive = false;
+            trctl.client.imp.rpc_version = client::GROUP_RPC_VERSION;
+            trctl.set_group(&qcmd, "slow".to_string()).unwrap();
+            assert_eq!(
+                trctl.client.imp.groups,
+                vec![(
+                    vec!["abed48adeb5e396f54a7089cbe6c1f2bc1b0dbc8".to_string()],
+                    "slow".to_string()
+                )]
+            );
+        });
+    }
+
+    #[test]
+    fn mock_speed() {
+        new_mock(|trctl| {
+            trctl.speed(Some(0), Some(500)).unwrap();
+            assert_eq!(trctl.client.imp.session_speed, vec![(Some(0), Some(500))]);
+            assert_eq!(
+                trctl.console.log.to_string().unwrap(),
+                "-- Download limit: disabled\n-- Upload limit: 500 KB/s\n"
+            );
+            assert!(trctl.speed(None, None).is_err());
+
+            // the limit left as is is shown too
+            trctl.speed(Some(100), None).unwrap();
+            assert!(trctl
+                .console
+                .log
+                .to_string()
+                .unwrap()
+                .ends_with("-- Download limit: 100 KB/s\n-- Upload limit: 500 KB/s\n"));
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected end of file")]
+    fn mock_erase_no_selection() {
+        new_valid_mock(|trctl, qcmd| {
+            log::set_max_level(log::LevelFilter::Info);
+            trctl.erase(qcmd, false).expect("ohno");
+            //assert_eq!(trctl.console.log.io.to_string().expect("heww"), "hello");
+        });
+    }
+
+    #[test]
+    fn mock_erase() {
+        new_valid_mock(|trctl, qcmd| {
+            trctl.console.input.input = "y\n".to_string();
+            log::set_max_level(log::LevelFilter::Info);
+            trctl.erase(qcmd, false).unwrap();
+            assert_eq!(trctl.console.log.to_string().unwrap(),
+            "ID     Done     Have     Size       ETA       Up     Down  Ratio    Age  Status     Name\n   \
+                1   100%     2.4G     2.4G   Unknown        0        0    0.8     NA  Stalled    dl//testing.pdf\n\
+             Sum:            2.4G                           0        0\n\
+             Select [y/N]: -- erase: testing.pdf\n"
+            );
+            //"ID     Done     Have     Size       ETA       Up     Down  Ratio    Age  Status     Name\n\
+            //70   100%     2.4G     2.4G   Unknown        0        0    0.8     NA  Stalled    dl//testing.pdf\n\
+        });
+    }
+
+    #[test]
+    fn mock_rm_freed() {
+        new_valid_mock(|trctl, qcmd| {
+            // no hash dir to remove
+            trctl.is_remote = true;
+            trctl.console.input.input = "y\n".to_string();
+            log::set_max_level(log::LevelFilter::Info);
+            trctl.erase(qcmd, true).unwrap();
+            assert!(trctl
+                .console
+                .log
+                .to_string()
+                .unwrap()
+                .ends_with("-- freed ~2.4G\n"));
+        });
+    }
+
+    #[test]
+    fn mock_rm_all() {
+        new_mock(|trctl| {
+            trctl.interactive = false;
+            let err = trctl.erase(QueryCmd::default(), true).unwrap_err();
+            assert!(err.to_string().contains("matches all 3 torrents"));
+
+            trctl.is_remote = true;
+            let qcmd = QueryCmd {
+                i_really_mean_it: true,
+                ..QueryCmd::default()
+            };
+            trctl.erase(qcmd, true).unwrap();
+        });
+    }
+
+    #[test]
+    fn mock_rm_remote() {
+        new_mock(|trctl| {
+            trctl.interactive = false;
+            // the mock torrents are in hash dirs
+            trctl.is_remote = true;
+            let qcmd = QueryCmd {
+                i_really_mean_it: true,
+                ..QueryCmd::default()
+            };
+            log::set_max_level(log::LevelFilter::Info);
+            trctl.erase(qcmd, true).unwrap();
+            let log = trctl.console.log.to_string().unwrap();
+            assert!(log.contains("-- skipped rmdir for 3 remote torrents\n"));
+            assert!(!log.contains("not removing"));
+        });
+    }
+
+    #[test]
+    fn mock_rm_threshold() {
+        new_mock(|trctl| {
+            trctl.rm_confirm_threshold = 2;
+            let qcmd = QueryCmd {
+                strs: vec!["testing".to_string()],
+                ..QueryCmd::default()
+            };
+            trctl.console.input.input = "a\n2\n".to_string();
+            let err = trctl.erase(qcmd.clone(), false).unwrap_err();
+            assert!(err.downcast_ref::<NothingToDo>().is_some());
+            assert!(trctl
+                .console
+                .log
+                .to_string()
+                .unwrap()
+                .contains("Type 3 to remove 3 torrents"));
+
+            trctl.console.input.input = "a\n3\n".to_string();
+            trctl.console.input.input_pos = 0;
+            trctl.erase(qcmd, false).unwrap();
+        });
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn mock_rm_symlink() {
+        new_valid_mock(|trctl, qcmd| {
+            trctl.console.input.input = "y\ny\n".to_string();
+            let dir = std::env::temp_dir().join(format!("trctl-symlink-{}", std::process::id()));
+            let target = dir.join("target");
+            std::fs::create_dir_all(&target).unwrap();
+            let hsh = trctl.client.imp.mock_data[0].hash_string.clone().unwrap();
+            let link = dir.join(hsh);
+            std::os::unix::fs::symlink(&target, &link).unwrap();
+            trctl.client.imp.mock_data[0].download_dir = Some(link.to_string_lossy().into());
+
+            let err = trctl.erase(qcmd.clone(), true).unwrap_err();
+            assert!(format!("{err:#}").contains("is a symlink"));
+            assert!(link.is_symlink());
+
+            trctl.follow_symlinks_on_rm = true;
+            trctl.erase(qcmd, true).unwrap();
+            assert!(!link.is_symlink());
+            assert!(target.is_dir());
+            std::fs::remove_dir_all(dir).unwrap();
+        });
+    }
+
+    #[test]
+    fn mock_mv_completed_files() {
+        new_valid_mock(|trctl, qcmd| {
+            trctl.interactive = false;
+            let dir = std::env::temp_dir().join(format!("trctl-mv-files-{}", std::process::id()));
+            let dldir = dir.join("dl");
+            let dst = dir.join("dst");
+            std::fs::create_dir_all(dldir.join("t")).unwrap();
+            std::fs::create_dir_all(&dst).unwrap();
+            std::fs::write(dldir.join("t/done"), "done").unwrap();
+            std::fs::write(dldir.join("t/part"), "pa").unwrap();
+            let file = |name: &str, bytes_completed| transmission_rpc::types::File {
+                length: 4,
+                bytes_completed,
+                name: name.into(),
+                begin_piece: None,
+                end_piece: None,
+            };
+            trctl.client.imp.mock_data[0].download_dir = Some(dldir.to_string_lossy().into());
+            trctl.client.imp.mock_data[0].files = Some(vec![file("t/done", 4), file("t/part", 2)]);
+
+            trctl.is_remote = true;
+            assert!(trctl.mv_completed_files(&qcmd, &dst).is_err());
+            trctl.is_remote = false;
+            trctl.mv_completed_files(&qcmd, &dst).unwrap();
+            assert_eq!(std::fs::read_to_string(dst.join("t/done")).unwrap(), "done");
+            assert!(dldir.join("t/done").is_symlink());
+            assert_eq!(
+                std::fs::read_to_string(dldir.join("t/done")).unwrap(),
+                "done"
+            );
+            assert!(!dst.join("t/part").exists());
+            // the links are skipped
+            trctl.mv_completed_files(&qcmd, &dst).unwrap();
+            std::fs::remove_dir_all(dir).unwrap();
+        });
+    }
+
+    #[test]
+    fn mock_erase_quiet() {
+        new_valid_mock(|trctl, qcmd| {
+            trctl.console.log.level = log::LevelFilter::Warn;
+            trctl.console.input.input = "y\n".to_string();
+            trctl.erase(qcmd, false).unwrap();
+            assert!(trctl
+                .console
+                .log
+                .to_string()
+                .unwrap()
+                .contains("dl//testing.pdf\n"));
+        });
+    }
+
+    #[test]
+    fn mock_erase_prompts() {
+        new_valid_mock(|trctl, qcmd| {
+            trctl.console.prompts = config::Prompts {
+                select: "Valitse".to_string(),
+                yes: "k".to_string(),
+                no: "e".to_string(),
+                all: "k".to_string(),
+            };
+            trctl.console.input.input = "x\nk\n".to_string();
+            log::set_max_level(log::LevelFilter::Info);
+            trctl.erase(qcmd, false).unwrap();
+            assert_eq!(trctl.console.log.to_string().unwrap(),
+            "ID     Done     Have     Size       ETA       Up     Down  Ratio    Age  Status     Name\n   \
+                1   100%     2.4G     2.4G   Unknown        0        0    0.8     NA  Stalled    dl//testing.pdf\n\
+             Sum:            2.4G                           0        0\n\
+             Valitse [k/E]: Valitse [k/E]: -- erase: testing.pdf\n\
+             -w Invalid selection 'x'\n"
             );
-            //"ID     Done     Have     Size       ETA       Up     Down  Ratio  Status     Name\n\
-            //70   100%     2.4G     2.4G   Unknown        0        0    0.8  Idle       dl//testing.pdf\n\
         });
     }
 
@@ -812,13 +3519,14 @@ mod tests {
             log::set_max_level(log::LevelFilter::Info);
             trctl.erase(qcmd, false).unwrap();
             assert_eq!(trctl.console.log.to_string().unwrap(),
-            "ID     Done     Have     Size       ETA       Up     Down  Ratio  Status     Name\n   \
-                1   100%     2.4G     2.4G   Unknown        0        0    0.8  Idle       dl//testing.pdf\n   \
-                2   100%     2.4G     2.4G   Unknown        0        0    0.8  Idle       dl//testing2.pdf\n       \
+            "ID     Done     Have     Size       ETA       Up     Down  Ratio    Age  Status     Name\n   \
+                1   100%     2.4G     2.4G   Unknown        0        0    0.8     NA  Stalled    dl//testing.pdf\n   \
+                2   100%     2.4G     2.4G   Unknown        0        0    0.8     NA  Stalled    dl//testing2.pdf\n       \
                     error: error!!!\n   \
-                3   100%     2.4G     2.4G   Unknown        0        0    0.8  Idle       dl//testing3.pdf\n\
+                3   100%     2.4G     2.4G   Unknown        0        0    0.8     NA  Stalled    dl//testing3.pdf\n\
              Sum:            7.1G                           0        0\n\
              Select [a/{n}/N]: Select [a/{n}/N]: Select [a/{n}/N]: Select [a/{n}/N]: -- erase: testing2.pdf\n\
+             -w matches all 3 torrents\n\
              -w invalid digit found in string\n\
              -w Invalid id\n\
              -w Invalid id\n"
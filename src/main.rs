@@ -1,40 +1,74 @@
 #![warn(clippy::all, clippy::pedantic)]
 #![allow(clippy::wildcard_imports)]
 
-mod escape;
-
 use std::ffi::OsStr;
-use std::io::{self, Write};
+use std::io::{self, Read as _, Write};
 // TODO querycmd out of lib
-use clap::{arg, command, value_parser, Args, Command, FromArgMatches as _, Parser, Subcommand};
+use clap::{
+    arg, command, value_parser, Args, Command, FromArgMatches as _, Parser, Subcommand, ValueEnum,
+};
 use clap_complete::{generate, Shell};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use url::Url;
 
-use trctl::client::{QueryCmd, Sort, TorrentAction, TorrentCli};
+use trctl::client::{
+    HashColumn, OutputFormat, PriorityLevel, QueryCmd, Sort, TorrentAction, TorrentCli,
+};
 use trctl::config::{Builder, BuilderOpts, Config};
 use trctl::console::{DefLog, Logger};
+use trctl::display::ByteSize;
 use trctl::errors::*;
-use trctl::{AddArgs, TorrentLoc};
+use trctl::escape;
+use trctl::torrent::Torrent as TorrentInfo;
+use trctl::{print_debug, print_log};
+use trctl::{AddArgs, OnDuplicate, TorrentLoc};
 
 const NAME: &str = env!("CARGO_PKG_NAME");
 
 #[derive(Parser, Debug)]
 #[command()]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Cli {
     /// Sen verbosity
     #[arg(long, short, action = clap::ArgAction::Count)]
     pub verbose: u8,
+    /// When to use colors. Overrides the config.
+    #[arg(long, value_enum)]
+    pub color: Option<ColorWhen>,
+    /// Same as --color never
+    #[arg(long, conflicts_with = "color")]
+    pub no_color: bool,
+    /// Only print warnings and errors
+    #[arg(long, short, conflicts_with = "verbose")]
+    pub quiet: bool,
     /// Run with a mock rpc client
     #[arg(long)]
     pub mock: bool,
     /// Don't ask for confirmation
     #[arg(long, short)]
     pub yes: bool,
+    /// Select torrents with fzf instead of by id when on a terminal
+    #[arg(long)]
+    pub pick: bool,
+    /// Show the hash as the first column of the table
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "short")]
+    pub show_hash: Option<HashColumn>,
+    /// Wait up to this many seconds for the daemon to answer before running the command, e.g.
+    /// when it was just started
+    #[arg(long, value_name = "SECONDS")]
+    pub wait_for_daemon: Option<u64>,
     #[command(subcommand)]
     pub cmd: Option<CliSub>,
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum ColorWhen {
+    /// When on a terminal and `NO_COLOR` is not set
+    Auto,
+    Always,
+    Never,
+}
+
 // See https://docs.rs/clap/latest/clap/_derive/index.html#terminology
 #[derive(Subcommand, Debug)]
 pub enum CliSub {
@@ -43,10 +77,35 @@ pub enum CliSub {
         /// Download directory
         #[arg(long)]
         dldir: Option<PathBuf>,
-        /// Whether the torrent already has files in the dldir
+        /// Whether the torrent already has files in the dldir. Defaults to
+        /// `default_use_existing` of the config
+        #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+        existing: Option<bool>,
+        /// Refuse to add if the info hash of the torrent is not this. Only with a single torrent
+        #[arg(long, value_parser = parse_hash)]
+        expect_hash: Option<String>,
+        /// Refuse to add instead of adding paused if there is not enough space
+        #[arg(long)]
+        no_overcommit: bool,
+        /// Label to set in addition to the configured `add_labels`
+        #[arg(long = "label")]
+        labels: Vec<String>,
+        /// What to do if the torrent has been added already
+        #[arg(long, value_enum, default_value_t)]
+        on_duplicate: OnDuplicate,
+        /// Add even if added before. Also set by the `TRCTL_NO_DEDUPE` environment variable
         #[arg(long)]
-        existing: bool,
-        /// Path to the torrent file
+        no_dedupe: bool,
+        /// Maximum number of peers of the torrent
+        #[arg(long)]
+        peer_limit: Option<i64>,
+        /// Bandwidth priority of the torrent
+        #[arg(long, value_enum)]
+        priority: Option<PriorityLevel>,
+        /// Number of add requests to send at a time. The space checks stay sequential
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=16))]
+        jobs: u8,
+        /// Path to the torrent file or a directory of them
         path: Vec<PathBuf>,
     },
     /// Add magnet link or a torrent file from url
@@ -54,9 +113,43 @@ pub enum CliSub {
         /// Download directory
         #[arg(long)]
         dldir: Option<PathBuf>,
-        /// Whether the torrent already has files in the dldir
+        /// Whether the torrent already has files in the dldir. Defaults to
+        /// `default_use_existing` of the config
+        #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+        existing: Option<bool>,
+        /// Refuse to add instead of adding paused if there is not enough space. Magnets are
+        /// estimated to be 5GiB
+        #[arg(long)]
+        no_overcommit: bool,
+        /// Label to set in addition to the configured `add_labels`
+        #[arg(long = "label")]
+        labels: Vec<String>,
+        /// What to do if the torrent has been added already
+        #[arg(long, value_enum, default_value_t)]
+        on_duplicate: OnDuplicate,
+        /// Add even if added before. Also set by the `TRCTL_NO_DEDUPE` environment variable
+        #[arg(long)]
+        no_dedupe: bool,
+        /// Maximum number of peers of the torrent
+        #[arg(long)]
+        peer_limit: Option<i64>,
+        /// Bandwidth priority of the torrent
+        #[arg(long, value_enum)]
+        priority: Option<PriorityLevel>,
+        /// Only fetch the metadata and print the files, then remove the torrent
+        #[arg(long)]
+        metadata_only: bool,
+        /// Leave the torrent stopped instead of removing it after --metadata-only
+        #[arg(long, requires = "metadata_only")]
+        keep: bool,
+        /// Cookie of the torrent file download. Visible to other users in the process list, so
+        /// prefer `http_headers` in the config
         #[arg(long)]
-        existing: bool,
+        cookie: Option<String>,
+        /// Header of the torrent file download as 'Name: value'. Added after the ones in
+        /// `http_headers` of the config
+        #[arg(long = "header")]
+        headers: Vec<String>,
         /// Url to a torrent file or a magnet link
         url: Vec<Url>,
     },
@@ -68,7 +161,14 @@ pub enum CliSub {
     /// Remove torrent but leave downloaded data in place
     Erase(QueryCmd),
     /// Clean finished torrents
+    // cleanable is implied
+    #[command(mut_arg("ratio", |a| a.requires(clap::builder::Resettable::Reset)))]
     Clean(QueryCmd),
+    /// Print the free and safe space of the download directories
+    Space {
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
     #[command(hide(true))]
     GenCompletions {
         /// Shell the completions are generated for
@@ -76,6 +176,30 @@ pub enum CliSub {
     },
     #[command(hide(true))]
     GenTorrents(QueryCmd),
+    /// Print version and build information
+    #[command(hide(true))]
+    Version {
+        /// Print as json
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the info hash of a torrent file without contacting the daemon
+    Hash {
+        /// Also print the size and the name, separated by tabs
+        #[arg(long, short)]
+        long: bool,
+        /// Path to the torrent file. - reads stdin.
+        path: PathBuf,
+    },
+    /// Send a raw rpc request and print the response. Needs `allow_raw_rpc` in the config.
+    #[command(hide(true))]
+    Rpc {
+        /// The rpc method, e.g. session-stats
+        method: String,
+        /// Arguments of the request as a json object
+        #[arg(long, value_parser = parse_json, default_value = "{}")]
+        args: serde_json::Value,
+    },
     /// Move torrents with the transmission rpc call
     SetLocation {
         #[command(flatten)]
@@ -86,6 +210,64 @@ pub enum CliSub {
         /// New location
         #[arg(long)]
         location: PathBuf,
+        /// Fail instead of warning if the files are not found in the new location when not
+        /// moving
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Set the bandwidth priority of torrents
+    Priority {
+        #[command(flatten)]
+        query_opts: QueryCmd,
+        #[arg(long, short, value_enum)]
+        level: PriorityLevel,
+    },
+    /// Set the download priority of the files of a torrent
+    FilePriority {
+        #[command(flatten)]
+        query_opts: QueryCmd,
+        /// Glob matched against the file paths, `*` also matches `/`
+        #[arg(long)]
+        file: String,
+        #[arg(long, short, value_enum)]
+        level: PriorityLevel,
+    },
+    /// Make torrents honor the session wide speed limits, or ignore them with --ignore
+    SessionLimits {
+        #[command(flatten)]
+        query_opts: QueryCmd,
+        /// Let the torrents go past the session wide speed limits
+        #[arg(long)]
+        ignore: bool,
+    },
+    /// Assign torrents to a bandwidth group
+    Group {
+        #[command(flatten)]
+        query_opts: QueryCmd,
+        /// Name of the group
+        #[arg(long)]
+        name: String,
+    },
+    /// Delete the data of torrents but keep them in transmission
+    WipeData(QueryCmd),
+    /// Copy the torrent files of torrents to <out>/<hash>.torrent
+    Export {
+        #[command(flatten)]
+        query_opts: QueryCmd,
+        /// Directory to copy the torrent files to
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Move torrents back to a download directory
+    MoveBack {
+        #[command(flatten)]
+        query_opts: QueryCmd,
+        /// Download directory. Defaults to the first configured one
+        #[arg(long)]
+        dldir: Option<PathBuf>,
+        /// Verify the torrents after moving
+        #[arg(long)]
+        verify: bool,
     },
     /// Move torrents
     Mv {
@@ -100,6 +282,12 @@ pub enum CliSub {
         /// Verify the files after move
         #[arg(long)]
         verify: Option<bool>,
+        /// Json prints the outcome of every torrent at the end
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+        /// Move only the completed files and leave links to them for the rest to download
+        #[arg(long, conflicts_with_all = ["force", "verify", "format"])]
+        completed_only: bool,
     },
     /// Queue torrents
     Start(QueryCmd),
@@ -107,36 +295,179 @@ pub enum CliSub {
     Stop(QueryCmd),
     /// Start torrents without queuing
     StartNow(QueryCmd),
+    /// Stop every active torrent with a single request
+    PauseAll,
+    /// Queue every stopped torrent with a single request
+    ResumeAll,
     /// Verify torrents
     Verify(QueryCmd),
     /// Reannounce torrents
-    Reannounce(QueryCmd),
+    Reannounce {
+        #[command(flatten)]
+        query_opts: QueryCmd,
+        /// Only the torrents with a failing tracker. Same as --tracker-failed
+        #[arg(long)]
+        failed_only: bool,
+    },
+    /// List the torrents with an error and offer to reannounce or verify them
+    Errors {
+        #[command(flatten)]
+        query_opts: QueryCmd,
+        /// Reannounce the torrents without asking
+        #[arg(long, conflicts_with = "verify")]
+        reannounce: bool,
+        /// Verify the torrents without asking
+        #[arg(long)]
+        verify: bool,
+    },
     /// List all trackers used by the torrents
     ListTrackers(QueryCmd),
+    /// Print or initialize the configuration
+    #[command(subcommand)]
+    Config(ConfigCmd),
+    /// Set the session wide speed limits
+    Speed {
+        /// Download limit in KB/s. 0 disables the limit
+        #[arg(long)]
+        down: Option<i64>,
+        /// Upload limit in KB/s. 0 disables the limit
+        #[arg(long)]
+        up: Option<i64>,
+    },
+}
+
+impl CliSub {
+    fn query_cmd_mut(&mut self) -> Option<&mut QueryCmd> {
+        match self {
+            CliSub::Query(qcmd)
+            | CliSub::Rm(qcmd)
+            | CliSub::Erase(qcmd)
+            | CliSub::Clean(qcmd)
+            | CliSub::GenTorrents(qcmd)
+            | CliSub::Start(qcmd)
+            | CliSub::Stop(qcmd)
+            | CliSub::StartNow(qcmd)
+            | CliSub::Verify(qcmd)
+            | CliSub::WipeData(qcmd)
+            | CliSub::Reannounce {
+                query_opts: qcmd, ..
+            }
+            | CliSub::ListTrackers(qcmd)
+            | CliSub::Errors {
+                query_opts: qcmd, ..
+            }
+            | CliSub::SetLocation {
+                query_opts: qcmd, ..
+            }
+            | CliSub::Priority {
+                query_opts: qcmd, ..
+            }
+            | CliSub::FilePriority {
+                query_opts: qcmd, ..
+            }
+            | CliSub::SessionLimits {
+                query_opts: qcmd, ..
+            }
+            | CliSub::MoveBack {
+                query_opts: qcmd, ..
+            }
+            | CliSub::Group {
+                query_opts: qcmd, ..
+            }
+            | CliSub::Export {
+                query_opts: qcmd, ..
+            }
+            | CliSub::Mv {
+                query_opts: qcmd, ..
+            } => Some(qcmd),
+            _ => None,
+        }
+    }
+
+    /// Whether the command may ask the user to select or confirm
+    fn prompts(&self) -> bool {
+        !matches!(
+            self,
+            CliSub::Query(_)
+                | CliSub::ListTrackers(_)
+                | CliSub::GenTorrents(_)
+                | CliSub::Export { .. }
+        )
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCmd {
+    /// Print the configuration in use with all the defaults filled in
+    Dump,
+    /// Write the default configuration to the config path
+    Init {
+        /// Overwrite an existing config
+        #[arg(long)]
+        force: bool,
+    },
 }
 
 #[allow(clippy::too_many_lines)]
-fn run<C: TorrentCli>(
+fn run<C: TorrentCli + Send>(
     builder: Builder<C>,
     cli: Cli,
     opts: &CustomOpts,
     mut log: DefLog,
 ) -> Result<()> {
+    if let Some(secs) = cli.wait_for_daemon {
+        builder
+            .new_client()?
+            .wait_for_daemon(std::time::Duration::from_secs(secs))?;
+    }
     if let Some(cmd) = cli.cmd {
         match cmd {
             CliSub::Add {
                 dldir,
                 path,
                 existing,
+                expect_hash,
+                no_overcommit,
+                labels,
+                on_duplicate,
+                no_dedupe,
+                peer_limit,
+                priority,
+                jobs,
             } => {
+                let path = torrent_paths(path, &mut log)?;
+                if expect_hash.is_some() && path.len() > 1 {
+                    bail!(
+                        "--expect-hash is for a single torrent, {} were given",
+                        path.len()
+                    );
+                }
+                let mut helpers = (1..jobs)
+                    .map(|_| builder.new_client())
+                    .collect::<Result<Vec<_>>>()?;
                 let mut t = builder.new_trmv(log)?;
-                let mut errors = 0;
-                for p in path {
-                    if let Err(err) = t.add(&AddArgs {
-                        location: &TorrentLoc::Path(p),
+                if no_dedupe {
+                    t.disable_dedupe();
+                }
+                let locations: Vec<TorrentLoc> = path.into_iter().map(TorrentLoc::Path).collect();
+                let args: Vec<AddArgs> = locations
+                    .iter()
+                    .map(|location| AddArgs {
+                        location,
                         dldir: dldir.as_ref(),
                         use_existing: existing,
-                    }) {
+                        expect_hash: expect_hash.as_deref(),
+                        no_overcommit,
+                        labels: &labels,
+                        on_duplicate,
+                        peer_limit,
+                        priority,
+                        headers: &[],
+                    })
+                    .collect();
+                let mut errors = 0;
+                for res in t.add_many(&args, &mut helpers) {
+                    if let Err(err) = res {
                         if err.downcast_ref::<NothingToDo>().is_some() {
                             t.view.log.print_result(&Err(err)).context("log")?;
                             errors += 1;
@@ -150,18 +481,50 @@ fn run<C: TorrentCli>(
                 }
                 Ok(())
             }
+            CliSub::AddUrl {
+                url,
+                metadata_only: true,
+                keep,
+                ..
+            } => {
+                let mut t = builder.new_trctl(log)?;
+                for u in url {
+                    t.metadata(&u, keep)?;
+                }
+                Ok(())
+            }
             CliSub::AddUrl {
                 dldir,
                 url,
                 existing,
+                no_overcommit,
+                labels,
+                on_duplicate,
+                no_dedupe,
+                peer_limit,
+                priority,
+                cookie,
+                mut headers,
+                ..
             } => {
+                headers.extend(cookie.map(|c| format!("Cookie: {c}")));
                 let mut t = builder.new_trmv(log)?;
+                if no_dedupe {
+                    t.disable_dedupe();
+                }
                 let mut errors = 0;
                 for u in url {
                     if let Err(err) = t.add(&AddArgs {
                         location: &TorrentLoc::Url(u),
                         dldir: dldir.as_ref(),
                         use_existing: existing,
+                        expect_hash: None,
+                        no_overcommit,
+                        labels: &labels,
+                        on_duplicate,
+                        peer_limit,
+                        priority,
+                        headers: &headers,
                     }) {
                         if err.downcast_ref::<NothingToDo>().is_some() {
                             t.view.log.print_result(&Err(err)).context("log")?;
@@ -180,25 +543,62 @@ fn run<C: TorrentCli>(
                 query_opts,
                 location,
                 mv,
+                strict,
             } => builder.new_trctl(log)?.set_location(
                 &query_opts,
                 mv,
                 location.to_string_lossy().to_string(),
+                strict,
             ),
+            CliSub::MoveBack {
+                query_opts,
+                dldir,
+                verify,
+            } => builder
+                .new_trctl(log)?
+                .move_back(&query_opts, dldir.as_deref(), verify),
+            CliSub::Group { query_opts, name } => {
+                builder.new_trctl(log)?.set_group(&query_opts, name)
+            }
+            CliSub::Priority { query_opts, level } => {
+                builder.new_trctl(log)?.set_priority(&query_opts, level)
+            }
+            CliSub::SessionLimits { query_opts, ignore } => builder
+                .new_trctl(log)?
+                .set_honors_session_limits(&query_opts, !ignore),
+            CliSub::FilePriority {
+                query_opts,
+                file,
+                level,
+            } => builder
+                .new_trctl(log)?
+                .file_priority(&query_opts, &file, level),
+            CliSub::Mv {
+                query_opts,
+                destination,
+                completed_only: true,
+                ..
+            } => builder
+                .new_trctl(log)?
+                .mv_completed_files(&query_opts, &destination),
             CliSub::Mv {
                 query_opts,
                 destination,
                 force,
                 verify,
+                format,
+                completed_only: false,
             } => builder.new_trctl(log)?.mv(
                 &query_opts,
                 destination.as_ref(),
                 force,
                 verify,
                 &opts.config,
+                format,
             ),
             CliSub::Query(args) => builder.new_trctl(log)?.query(&args),
             CliSub::ListTrackers(args) => builder.new_trctl(log)?.list_trackers(&args),
+            CliSub::Speed { down, up } => builder.new_trctl(log)?.speed(down, up),
             CliSub::Rm(args) => builder.new_trctl(log)?.erase(args, true),
             CliSub::Erase(args) => builder.new_trctl(log)?.erase(args, false),
             CliSub::Clean(mut args) => {
@@ -206,14 +606,44 @@ fn run<C: TorrentCli>(
                 builder.new_trctl(log)?.erase(args, false)
             }
             CliSub::Verify(args) => builder.new_trctl(log)?.action(&args, TorrentAction::Verify),
+            CliSub::WipeData(args) => builder.new_trctl(log)?.wipe_data(&args),
+            CliSub::Export { query_opts, out } => builder.new_trctl(log)?.export(&query_opts, &out),
             CliSub::Start(args) => builder.new_trctl(log)?.action(&args, TorrentAction::Start),
             CliSub::StartNow(args) => builder
                 .new_trctl(log)?
                 .action(&args, TorrentAction::StartNow),
             CliSub::Stop(args) => builder.new_trctl(log)?.action(&args, TorrentAction::Stop),
-            CliSub::Reannounce(args) => builder
-                .new_trctl(log)?
-                .action(&args, TorrentAction::Reannounce),
+            CliSub::PauseAll => builder.new_trctl(log)?.action_all(TorrentAction::Stop),
+            CliSub::ResumeAll => builder.new_trctl(log)?.action_all(TorrentAction::Start),
+            CliSub::Errors {
+                query_opts,
+                reannounce,
+                verify,
+            } => {
+                let recover = if reannounce {
+                    Some(TorrentAction::Reannounce)
+                } else if verify {
+                    Some(TorrentAction::Verify)
+                } else {
+                    None
+                };
+                builder.new_trctl(log)?.errors(&query_opts, recover)
+            }
+            CliSub::Reannounce {
+                mut query_opts,
+                failed_only,
+            } => {
+                query_opts.tracker_failed |= failed_only;
+                builder
+                    .new_trctl(log)?
+                    .action(&query_opts, TorrentAction::Reannounce)
+            }
+            CliSub::Space { format } => {
+                let mut t = builder.new_trmv(log)?;
+                let dirs = t.space()?;
+                let quota = t.quota;
+                t.view.print_space(&dirs, quota, format)
+            }
             CliSub::GenTorrents(mut args) => {
                 //println!("{:?}", args.strs);
                 let mut client = builder.new_client()?;
@@ -256,7 +686,11 @@ fn run<C: TorrentCli>(
                 }
                 Ok(())
             }
-            CliSub::GenCompletions { .. } => {
+            CliSub::GenCompletions { .. }
+            | CliSub::Version { .. }
+            | CliSub::Config(_)
+            | CliSub::Hash { .. }
+            | CliSub::Rpc { .. } => {
                 bail!("should not happen");
             }
         }
@@ -267,6 +701,30 @@ fn run<C: TorrentCli>(
     }
 }
 
+// Directories are replaced with the .torrent files in them in sorted order
+fn torrent_paths(paths: Vec<PathBuf>, log: &mut DefLog) -> Result<Vec<PathBuf>> {
+    let mut res = Vec::with_capacity(paths.len());
+    for p in paths {
+        if !p.is_dir() {
+            res.push(p);
+            continue;
+        }
+        let mut entries = std::fs::read_dir(&p)
+            .with_context(|| format!("read_dir {}", p.display()))?
+            .map(|e| Ok(e?.path()))
+            .collect::<Result<Vec<PathBuf>>>()?;
+        entries.sort();
+        for e in entries {
+            if e.is_file() && e.extension().is_some_and(|ext| ext == "torrent") {
+                res.push(e);
+            } else {
+                print_debug!(&mut *log, "skipping {}", e.display())?;
+            }
+        }
+    }
+    Ok(res)
+}
+
 #[derive(Args)]
 struct CustomOpts {
     config: PathBuf,
@@ -277,7 +735,7 @@ fn build_cli() -> Result<Command> {
     let default_cfgpath: &'static OsStr =
         Box::leak(Config::config_path(NAME)?.into_boxed_path()).as_os_str();
     let parser = parser.arg(
-        arg!(-c --config <CONFIG> "Configuration file")
+        arg!(-c --config <CONFIG> "Configuration file, toml or json by the extension")
             .value_parser(value_parser!(PathBuf))
             .default_value(default_cfgpath),
     );
@@ -285,15 +743,95 @@ fn build_cli() -> Result<Command> {
     Ok(Cli::augment_args(parser))
 }
 
+fn print_version(json: bool) -> Result<()> {
+    let sqlite = cfg!(feature = "sqlite");
+    let mut out = io::stdout();
+    if json {
+        let info = serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "git_describe": env!("BUILD_FULL_VERSION"),
+            "rustc": env!("BUILD_RUSTC_VERSION"),
+            "features": {
+                "sqlite": sqlite,
+            },
+        });
+        writeln!(out, "{info}")?;
+    } else {
+        writeln!(out, "version: {}", env!("CARGO_PKG_VERSION"))?;
+        writeln!(out, "git describe: {}", env!("BUILD_FULL_VERSION"))?;
+        writeln!(out, "rustc: {}", env!("BUILD_RUSTC_VERSION"))?;
+        writeln!(out, "sqlite: {}", if sqlite { "on" } else { "off" })?;
+    }
+    Ok(())
+}
+
+fn print_hash(path: &Path, long: bool) -> Result<()> {
+    let content = if path.as_os_str() == "-" {
+        let mut content = Vec::new();
+        io::stdin().read_to_end(&mut content)?;
+        content
+    } else {
+        std::fs::read(path).with_context(|| format!("reading {}", path.display()))?
+    };
+    let torrent = TorrentInfo::from_bytes(&content)?;
+    let mut out = io::stdout();
+    if long {
+        writeln!(
+            out,
+            "{}\t{:.1}\t{}",
+            torrent.info_hash,
+            ByteSize(torrent.length),
+            String::from_utf8_lossy(&torrent.name)
+        )?;
+    } else {
+        writeln!(out, "{}", torrent.info_hash)?;
+    }
+    Ok(())
+}
+
+// Lowercase like the computed ones
+fn parse_hash(s: &str) -> Result<String> {
+    if s.len() != 40 || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("expected 40 hex characters");
+    }
+    Ok(s.to_lowercase())
+}
+
+fn parse_json(s: &str) -> Result<serde_json::Value> {
+    serde_json::from_str(s).context("invalid json")
+}
+
 fn run_logged() -> Result<()> {
     let parser = build_cli()?;
     let matches = parser.get_matches();
-    let cli = Cli::from_arg_matches(&matches)?;
+    let mut cli = Cli::from_arg_matches(&matches)?;
     let opts = CustomOpts::from_arg_matches(&matches)?;
 
+    // before loading as that would create the file
+    if let Some(CliSub::Config(ConfigCmd::Init { force })) = cli.cmd {
+        Config::init_path(&opts.config, force)?;
+        writeln!(io::stdout(), "Wrote {}", opts.config.display())?;
+        return Ok(());
+    }
+
     let cfg = Config::load_path(&opts.config)?;
 
-    let log = DefLog::from_choice(cfg.color, cli.verbose);
+    if let Some(CliSub::Config(ConfigCmd::Dump)) = cli.cmd {
+        write!(io::stdout(), "{}", cfg.to_toml()?)?;
+        return Ok(());
+    }
+
+    let color = match cli.color {
+        _ if cli.no_color => Some(false),
+        Some(ColorWhen::Always) => Some(true),
+        Some(ColorWhen::Never) => Some(false),
+        Some(ColorWhen::Auto) => None,
+        None => cfg.color,
+    };
+    let mut log = DefLog::from_choice(color, cli.verbose);
+    if cli.quiet {
+        log.level = log::LevelFilter::Warn;
+    }
     if std::env::var("RUST_LOG").is_ok() {
         log.register_debug();
     }
@@ -303,8 +841,44 @@ fn run_logged() -> Result<()> {
         return Ok(());
     }
 
+    if let Some(CliSub::Version { json }) = cli.cmd {
+        return print_version(json);
+    }
+
+    if let Some(CliSub::Hash { long, ref path }) = cli.cmd {
+        return print_hash(path, long);
+    }
+
+    if let Some(CliSub::Rpc {
+        ref method,
+        ref args,
+    }) = cli.cmd
+    {
+        if !cfg.allow_raw_rpc {
+            bail!(
+                "Raw rpc requests are not validated, set allow_raw_rpc in the config to use them"
+            );
+        }
+        if cli.mock {
+            bail!("rpc does not work with --mock");
+        }
+        let res = cfg.builder(NAME).default_client()?.raw_rpc(method, args)?;
+        serde_json::to_writer_pretty(io::stdout(), &res)?;
+        writeln!(io::stdout())?;
+        return Ok(());
+    }
+
+    let prompting = !cli.yes && cli.cmd.as_ref().is_some_and(CliSub::prompts);
+    if let Some(qcmd) = cli.cmd.as_mut().and_then(CliSub::query_cmd_mut) {
+        qcmd.smart_ids |= cfg.smart_ids;
+        qcmd.apply_smart_ids();
+        qcmd.read_ids_from(prompting)?;
+    }
+
     let builder_opts = BuilderOpts {
         interactive: !cli.yes,
+        pick: cli.pick,
+        show_hash: cli.show_hash,
     };
 
     if cli.mock {
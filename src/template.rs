@@ -0,0 +1,180 @@
+//! `--template` output where `{field}` is replaced by the field of each torrent
+use crate::display::{ByteSize, Eta, Maybe, Status, Torrent as DisplayTorrent};
+use std::fmt::{self, Write as _};
+use transmission_rpc::types::Torrent;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Id,
+    Hash,
+    Name,
+    Done,
+    Have,
+    Size,
+    Eta,
+    Up,
+    Down,
+    Ratio,
+    Age,
+    SeedTime,
+    Status,
+    Dir,
+}
+
+impl Field {
+    const ALL: [(&'static str, Field); 14] = [
+        ("id", Field::Id),
+        ("hash", Field::Hash),
+        ("name", Field::Name),
+        ("done", Field::Done),
+        ("have", Field::Have),
+        ("size", Field::Size),
+        ("eta", Field::Eta),
+        ("up", Field::Up),
+        ("down", Field::Down),
+        ("ratio", Field::Ratio),
+        ("age", Field::Age),
+        ("seed_time", Field::SeedTime),
+        ("status", Field::Status),
+        ("dir", Field::Dir),
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find_map(|&(n, field)| (n == name).then_some(field))
+    }
+
+    fn write(self, out: &mut String, dt: &DisplayTorrent) -> fmt::Result {
+        let tor = dt.torrent;
+        match self {
+            Field::Id => write!(out, "{}", dt.id()),
+            Field::Hash => write!(out, "{}", Maybe(tor.hash_string.as_ref(), false)),
+            Field::Name => write!(out, "{}", Maybe(tor.name.as_ref(), false)),
+            Field::Done => write!(out, "{:.0}%", dt.percent_done()),
+            Field::Have => write!(out, "{:.1}", dt.downloaded_size()),
+            Field::Size => write!(out, "{:.1}", Maybe(tor.size_when_done.map(ByteSize), true)),
+            Field::Eta => write!(
+                out,
+                "{}",
+                Maybe(
+                    tor.eta.map(|eta| Eta {
+                        eta,
+                        left_until_done: tor.left_until_done
+                    }),
+                    true
+                )
+            ),
+            Field::Up => write!(out, "{:.1}", Maybe(tor.rate_upload.map(ByteSize), true)),
+            Field::Down => write!(out, "{:.1}", Maybe(tor.rate_download.map(ByteSize), true)),
+            Field::Ratio => write!(out, "{:.1}", dt.ratio()),
+            Field::Age => write!(out, "{}", dt.age()),
+            Field::SeedTime => write!(out, "{}", dt.seed_time()),
+            Field::Status => write!(out, "{}", Maybe(Status::from_torrent(tor), false)),
+            Field::Dir => write!(out, "{}", dt.download_dir()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Field(Field),
+}
+
+/// A parsed template. `{{` and `}}` are literal braces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Fails on unknown fields and unbalanced braces
+    pub fn parse(s: &str) -> std::result::Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed {{{name}")),
+                        }
+                    }
+                    let field = Field::from_name(&name).ok_or_else(|| {
+                        let valid: Vec<&str> = Field::ALL.iter().map(|&(n, _)| n).collect();
+                        format!("unknown field {{{name}}}, valid: {}", valid.join(", "))
+                    })?;
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(field));
+                }
+                '}' => return Err("unmatched }, use }} for a literal one".to_string()),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Self { parts })
+    }
+
+    #[must_use]
+    pub fn render(&self, dt: &DisplayTorrent) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(s) => out.push_str(s),
+                // writing to a string does not fail
+                Part::Field(field) => field.write(&mut out, dt).unwrap_or(()),
+            }
+        }
+        out
+    }
+
+    /// Renders with the torrent on its own
+    #[must_use]
+    pub fn render_torrent(&self, torrent: &Torrent, base_dir: &std::path::Path) -> String {
+        self.render(&DisplayTorrent { torrent, base_dir })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::test_torrent;
+    use std::path::Path;
+
+    #[test]
+    fn render() {
+        let tor = test_torrent(1, "testing.pdf");
+        let template = Template::parse("{id}\t{name} {{{done}}} {ratio}").unwrap();
+        assert_eq!(
+            template.render_torrent(&tor, Path::new("/var/cache/torrents")),
+            "1\ttesting.pdf {100%} 0.8"
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = Template::parse("{nope}").unwrap_err();
+        assert!(err.contains("unknown field {nope}"));
+        assert!(err.contains("id, hash, name"));
+        assert!(Template::parse("{id").is_err());
+        assert!(Template::parse("id}").is_err());
+        assert_eq!(Template::parse("").unwrap().parts, []);
+    }
+}
@@ -3,19 +3,23 @@ use std::os::unix::ffi::OsStrExt;
 use bendy::decoding::{FromBencode as _, ResultExt as _};
 use sha1::{Digest as _, Sha1};
 
-use crate::errors::anyhow;
+use crate::errors::{TorrentParseError, TorrentParseStage};
 
 #[derive(Debug)]
 pub struct Torrent {
     pub info_hash: String,
     pub length: u64,
     pub name: Vec<u8>,
+    /// From announce and announce-list
+    pub trackers: Vec<String>,
+    /// Tracker entries that were not urls and were left out of `trackers`
+    pub skipped_trackers: usize,
 }
 
 #[derive(Debug)]
 enum TorrentError {
     Bendy(bendy::decoding::Error),
-    Custom(&'static str),
+    Parse(TorrentParseError),
 }
 
 impl From<bendy::decoding::Error> for TorrentError {
@@ -26,61 +30,110 @@ impl From<bendy::decoding::Error> for TorrentError {
 
 impl Torrent {
     pub fn from_bytes(bytes: &[u8]) -> crate::errors::Result<Self> {
-        Self::from_bytes_doit(bytes).map_err(|err| match err {
-            TorrentError::Bendy(e) => anyhow!("bendy: {}", e),
-            TorrentError::Custom(e) => anyhow!("torrent error: {}", e),
+        let mut stage = TorrentParseStage::Torrent;
+        Self::from_bytes_doit(bytes, &mut stage).map_err(|err| {
+            match err {
+                TorrentError::Bendy(error) => TorrentParseError::Bendy { stage, error },
+                TorrentError::Parse(e) => e,
+            }
+            .into()
         })
     }
 
-    fn from_bytes_doit(bytes: &[u8]) -> Result<Self, TorrentError> {
+    /// `stage` is kept up to date so that a decoding error can be attributed to the part of the
+    /// torrent that was being parsed.
+    #[allow(clippy::too_many_lines)]
+    fn from_bytes_doit(bytes: &[u8], stage: &mut TorrentParseStage) -> Result<Self, TorrentError> {
         let mut decoder = bendy::decoding::Decoder::new(bytes);
         let mut info_hash = None;
         let mut length = None;
         let mut name = None;
+        let mut trackers = Vec::new();
+        let mut skipped_trackers = 0;
 
         match decoder.next_object().context("next_object")? {
-            None => return Err(TorrentError::Custom("eof")),
+            None => return Err(TorrentError::Parse(TorrentParseError::Empty)),
             Some(obj) => {
                 let mut dict = obj.try_into_dictionary().context("torrent object")?;
                 while let Some(pair) = dict.next_pair().context("dict pair")? {
-                    if let (b"info", value) = pair {
-                        let mut infodict = value.try_into_dictionary().context("info value")?;
-                        while let Some(infopair) = infodict.next_pair().context("dict pair")? {
-                            match infopair {
-                                (b"name", value) => {
-                                    name.replace(value.try_into_bytes().context("name")?.to_vec());
-                                }
-                                (b"length", value) => {
-                                    length.replace(
-                                        u64::decode_bencode_object(value).context("length")?,
-                                    );
-                                }
-                                (b"files", value) => {
-                                    if length.is_none() {
-                                        let mut files =
-                                            value.try_into_list().context("invalid list")?;
-                                        let mut len: u64 = 0;
-                                        while let Some(file) =
-                                            files.next_object().context("next file")?
+                    match pair {
+                        (b"announce", value) => {
+                            *stage = TorrentParseStage::Announce;
+                            match value.try_into_bytes() {
+                                Ok(url) => push_tracker(&mut trackers, url),
+                                Err(_) => skipped_trackers += 1,
+                            }
+                            *stage = TorrentParseStage::Torrent;
+                        }
+                        (b"announce-list", value) => {
+                            *stage = TorrentParseStage::Announce;
+                            // Junk entries are common, skip them rather than refusing the torrent
+                            match value.try_into_list() {
+                                Ok(mut tiers) => {
+                                    while let Some(tier) = tiers.next_object().context("tier")? {
+                                        let Ok(mut urls) = tier.try_into_list() else {
+                                            skipped_trackers += 1;
+                                            continue;
+                                        };
+                                        while let Some(url) =
+                                            urls.next_object().context("tracker")?
                                         {
-                                            let mut file = file
-                                                .try_into_dictionary()
-                                                .context("invalid file")?;
-                                            while let Some(pair) =
-                                                file.next_pair().context("file dict pair")?
+                                            match url.try_into_bytes() {
+                                                Ok(url) => push_tracker(&mut trackers, url),
+                                                Err(_) => skipped_trackers += 1,
+                                            }
+                                        }
+                                    }
+                                }
+                                Err(_) => skipped_trackers += 1,
+                            }
+                            *stage = TorrentParseStage::Torrent;
+                        }
+                        (b"info", value) => {
+                            *stage = TorrentParseStage::Info;
+                            let mut infodict = value.try_into_dictionary().context("info value")?;
+                            while let Some(infopair) = infodict.next_pair().context("dict pair")? {
+                                match infopair {
+                                    (b"name", value) => {
+                                        *stage = TorrentParseStage::Name;
+                                        name.replace(
+                                            value.try_into_bytes().context("name")?.to_vec(),
+                                        );
+                                    }
+                                    (b"length", value) => {
+                                        *stage = TorrentParseStage::Length;
+                                        length.replace(
+                                            u64::decode_bencode_object(value).context("length")?,
+                                        );
+                                    }
+                                    (b"files", value) => {
+                                        *stage = TorrentParseStage::Files;
+                                        if length.is_none() {
+                                            let mut files =
+                                                value.try_into_list().context("invalid list")?;
+                                            let mut len: u64 = 0;
+                                            while let Some(file) =
+                                                files.next_object().context("next file")?
                                             {
-                                                match pair {
-                                                    (b"path", value) => {
-                                                        if name.is_none() {
-                                                            let mut path_components = value
-                                                                .try_into_list()
-                                                                .context("path components")?;
-                                                            let mut pb = std::path::PathBuf::new();
-                                                            while let Some(pc) = path_components
-                                                                .next_object()
-                                                                .context("path component")?
-                                                            {
-                                                                pb.push(std::path::Path::new(
+                                                let mut file = file
+                                                    .try_into_dictionary()
+                                                    .context("invalid file")?;
+                                                while let Some(pair) =
+                                                    file.next_pair().context("file dict pair")?
+                                                {
+                                                    match pair {
+                                                        (b"path", value) => {
+                                                            if name.is_none() {
+                                                                let mut path_components = value
+                                                                    .try_into_list()
+                                                                    .context("path components")?;
+                                                                let mut pb =
+                                                                    std::path::PathBuf::new();
+                                                                while let Some(pc) = path_components
+                                                                    .next_object()
+                                                                    .context("path component")?
+                                                                {
+                                                                    pb.push(std::path::Path::new(
                                                                     std::ffi::OsStr::from_bytes(
                                                                         pc.try_into_bytes()
                                                                             .context(
@@ -88,45 +141,129 @@ impl Torrent {
                                                                         )?,
                                                                     ),
                                                                 ));
+                                                                }
+                                                                name.replace(
+                                                                    pb.as_os_str()
+                                                                        .as_bytes()
+                                                                        .to_vec(),
+                                                                );
                                                             }
-                                                            name.replace(
-                                                                pb.as_os_str().as_bytes().to_vec(),
-                                                            );
                                                         }
-                                                    }
-                                                    (b"length", value) => {
-                                                        len = len
-                                                            .checked_add(
-                                                                u64::decode_bencode_object(value)
+                                                        (b"length", value) => {
+                                                            len = len
+                                                                .checked_add(
+                                                                    u64::decode_bencode_object(
+                                                                        value,
+                                                                    )
                                                                     .context("invalid u64")?,
-                                                            )
-                                                            .expect("length overflowed");
+                                                                )
+                                                                .expect("length overflowed");
+                                                        }
+                                                        (_, _) => {}
                                                     }
-                                                    (_, _) => {}
                                                 }
                                             }
+                                            length.replace(len);
                                         }
-                                        length.replace(len);
                                     }
+                                    (_, _) => {}
                                 }
-                                (_, _) => {}
+                                *stage = TorrentParseStage::Info;
                             }
-                        }
 
-                        let mut hasher = Sha1::new();
-                        let infobytes = infodict.into_raw().context("info dict")?;
-                        hasher.update(infobytes);
-                        info_hash.replace(format!("{:x}", hasher.finalize()));
+                            *stage = TorrentParseStage::InfoHash;
+                            let mut hasher = Sha1::new();
+                            let infobytes = infodict.into_raw().context("info dict")?;
+                            hasher.update(infobytes);
+                            info_hash.replace(format!("{:x}", hasher.finalize()));
+                            *stage = TorrentParseStage::Torrent;
+                        }
+                        (_, _) => {}
                     }
                 }
             }
         }
 
         Ok(Torrent {
-            info_hash: info_hash
-                .ok_or(TorrentError::Custom("info hash could not be calculated"))?,
-            length: length.ok_or(TorrentError::Custom("length could not be calculated"))?,
-            name: name.ok_or(TorrentError::Custom("name could not be found"))?,
+            info_hash: info_hash.ok_or(TorrentError::Parse(TorrentParseError::Missing(
+                TorrentParseStage::Info,
+            )))?,
+            length: length.ok_or(TorrentError::Parse(TorrentParseError::Missing(
+                TorrentParseStage::Length,
+            )))?,
+            name: name.ok_or(TorrentError::Parse(TorrentParseError::Missing(
+                TorrentParseStage::Name,
+            )))?,
+            trackers,
+            skipped_trackers,
         })
     }
 }
+
+// The same tracker is often both the announce and in the announce-list
+fn push_tracker(trackers: &mut Vec<String>, url: &[u8]) {
+    let url = String::from_utf8_lossy(url).into_owned();
+    if !trackers.contains(&url) {
+        trackers.push(url);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_err(bytes: &[u8]) -> TorrentParseError {
+        Torrent::from_bytes(bytes)
+            .unwrap_err()
+            .downcast::<TorrentParseError>()
+            .unwrap()
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(matches!(parse_err(b""), TorrentParseError::Empty));
+        assert!(matches!(
+            parse_err(b"d4:infod6:lengthi1e4:namei1eee"),
+            TorrentParseError::Bendy {
+                stage: TorrentParseStage::Name,
+                ..
+            }
+        ));
+        assert!(matches!(
+            parse_err(b"d4:infod6:length1:a4:name1:aee"),
+            TorrentParseError::Bendy {
+                stage: TorrentParseStage::Length,
+                ..
+            }
+        ));
+        assert!(matches!(
+            parse_err(b"d4:infod6:lengthi1eee"),
+            TorrentParseError::Missing(TorrentParseStage::Name)
+        ));
+    }
+
+    #[test]
+    fn parse() {
+        let t = Torrent::from_bytes(b"d4:infod6:lengthi3e4:name1:aee").unwrap();
+        assert_eq!(t.name, b"a");
+        assert_eq!(t.length, 3);
+        assert!(t.trackers.is_empty());
+    }
+
+    #[test]
+    fn parse_trackers() {
+        let t = Torrent::from_bytes(
+            b"d8:announce5:http113:announce-listll5:http1el5:http2ee4:infod6:lengthi3e4:name1:aee",
+        )
+        .unwrap();
+        assert_eq!(t.trackers, ["http1", "http2"]);
+        assert_eq!(t.skipped_trackers, 0);
+
+        let t = Torrent::from_bytes(
+            b"d8:announcei1e13:announce-listli2el5:http1leee4:infod6:lengthi3e4:name1:aee",
+        )
+        .unwrap();
+        assert_eq!(t.trackers, ["http1"]);
+        assert_eq!(t.skipped_trackers, 3);
+    }
+}